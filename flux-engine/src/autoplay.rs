@@ -0,0 +1,83 @@
+use std::f32::consts::PI;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{mods::FluxMods, note_position};
+
+/// how the autoplay cursor moves from one note to the next
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FluxEasing {
+    Linear,
+    #[default]
+    Cubic,
+    /// overshoots a little and settles on the note
+    Spring,
+}
+
+impl FluxEasing {
+    pub const ALL: [FluxEasing; 3] = [FluxEasing::Linear, FluxEasing::Cubic, FluxEasing::Spring];
+
+    /// eased progress for `t` from 0 to 1, always 0 at the start and 1 at the end
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            FluxEasing::Linear => t,
+            FluxEasing::Cubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            // damped oscillation with a zero crossing exactly at t = 1
+            FluxEasing::Spring => 1.0 - (-5.0 * t).exp() * (2.5 * PI * t).cos(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxEasing::Linear => "Linear",
+            FluxEasing::Cubic => "Cubic",
+            FluxEasing::Spring => "Spring",
+        }
+    }
+}
+
+/// Moves a cursor through the notes of a difficulty so it is on every note at its time.
+#[derive(Clone, Debug)]
+pub struct FluxAutoplay {
+    /// note time and position relative to the play area
+    targets: Vec<(f64, f32, f32)>,
+    pub easing: FluxEasing,
+}
+
+impl FluxAutoplay {
+    /// `mods` have to match the played ones so mirrored notes are followed
+    pub fn new(notes: &[flux_map::FluxNote], easing: FluxEasing, mods: &FluxMods) -> Self {
+        let mut targets: Vec<(f64, f32, f32)> = notes.iter().map(|n| {
+            let (x, y) = mods.note_position(n.x, n.y);
+            let (x, y) = note_position(x, y);
+            (n.time as f64, x, y)
+        }).collect();
+        targets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            targets,
+            easing,
+        }
+    }
+
+    /// cursor position at song time `ms`, relative to the play area
+    pub fn cursor_at(&self, ms: f64) -> (f32, f32) {
+        let next = match self.targets.iter().position(|t| t.0 > ms) {
+            Some(next) => next,
+            None => return self.targets.last().map_or((0.0, 0.0), |t| (t.1, t.2)),
+        };
+        if next == 0 {
+            return (self.targets[0].1, self.targets[0].2);
+        }
+        let (from_ms, from_x, from_y) = self.targets[next - 1];
+        let (to_ms, to_x, to_y) = self.targets[next];
+        let f = self.easing.ease(((ms - from_ms) / (to_ms - from_ms)) as f32);
+        (from_x + (to_x - from_x) * f, from_y + (to_y - from_y) * f)
+    }
+}
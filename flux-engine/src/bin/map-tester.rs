@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use flux_engine::{FluxEngine, FluxEngineConfig, approach_time_ms, autoplay::{FluxAutoplay, FluxEasing}, health::FluxHealthConfig, judgement::FluxJudgementConfig, mods::FluxMods};
+use flux_map::{FluxMap, replay::FluxReplayFrame};
+
+/// plays every difficulty of a map with autoplay and reports notes it couldn't hit
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct CliArguments {
+    map_path : PathBuf,
+    /// only test this difficulty
+    #[arg(short,long)]
+    difficulty : Option<String>,
+    #[arg(short,long,value_enum,default_value_t=Easing::Cubic)]
+    easing : Easing,
+    /// frames per second the map is stepped at
+    #[arg(short,long,default_value_t=144.0)]
+    fps : f64,
+    #[arg(long,default_value_t=10.0)]
+    ar : f32,
+    #[arg(long,default_value_t=6.0)]
+    ad : f32,
+    #[arg(long,default_value_t=1.14)]
+    hitbox : f32,
+    #[arg(long,default_value_t=1.0)]
+    speed : f64,
+}
+#[derive(Clone,Copy,ValueEnum)]
+enum Easing {
+    Linear,
+    Cubic,
+    Spring,
+}
+impl From<Easing> for FluxEasing {
+    fn from(e: Easing) -> Self {
+        match e {
+            Easing::Linear => FluxEasing::Linear,
+            Easing::Cubic => FluxEasing::Cubic,
+            Easing::Spring => FluxEasing::Spring,
+        }
+    }
+}
+
+fn main() {
+    let args = CliArguments::parse();
+    let map = FluxMap::open(args.map_path).expect("Failed to open map");
+    let config = FluxEngineConfig {
+        approach_time: approach_time_ms(args.ar, args.ad),
+        ad: args.ad,
+        hitbox: args.hitbox,
+        speed: args.speed,
+        judgement: FluxJudgementConfig::default(),
+        health: FluxHealthConfig::default(),
+        mods: FluxMods::default(),
+    };
+    let mut names : Vec<&String> = map.difficulties.keys().filter(|d| args.difficulty.as_ref().is_none_or(|n| n == *d)).collect();
+    names.sort();
+    for name in names {
+        let notes = &map.difficulties[name];
+        let autoplay = FluxAutoplay::new(notes, args.easing.into(), &config.mods);
+        let end = FluxMap::length_ms(notes) as f64 + config.judgement.good + 1000.0;
+        let frame_ms = 1000.0 / args.fps;
+        let inputs : Vec<FluxReplayFrame> = (0..=(end / frame_ms) as u64).map(|i| {
+            let ms = i as f64 * frame_ms;
+            let (x, y) = autoplay.cursor_at(ms);
+            FluxReplayFrame { ms, x, y }
+        }).collect();
+        let mut config = config.clone();
+        config.health = config.health.for_map(&map, name);
+        let stats = FluxEngine::simulate(config, notes, &inputs);
+        println!("{}: {}/{} hit, {:.2}% accuracy, {} score", name, stats.notes_hit, notes.len(), stats.accuracy(), stats.score);
+        for miss in stats.hits.iter().filter(|h| h.error.is_none()) {
+            println!("  missed note {} at {}ms", miss.note_index, miss.note_ms);
+        }
+    }
+}
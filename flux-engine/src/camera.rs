@@ -0,0 +1,46 @@
+// nothing closer to the camera than this many hit plane distances is drawn
+const NEAR: f32 = 0.01;
+
+/// A perspective projection from in front of the play area.
+/// Positions are in pixels from the middle of the play area and depths in hit plane distances,
+/// the hit plane is `HIT_PLANE` away and notes are `FluxEngineNote::depth` away.
+/// The cursor is on the hit plane and a note is there at its time, so the note that gets judged
+/// against the cursor is drawn under it wherever the camera looks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FluxProjection {
+    /// in pixels, also the distance to the hit plane
+    focal: f32,
+    position: (f32, f32),
+    yaw: f32,
+    pitch: f32,
+}
+
+impl FluxProjection {
+    /// a camera `focal` pixels in front of `position` on the hit plane, turned to look at `target` on it
+    pub fn look_at(focal: f32, position: (f32, f32), target: (f32, f32)) -> Self {
+        let to = (target.0 - position.0, target.1 - position.1);
+        Self {
+            focal,
+            position,
+            yaw: to.0.atan2(focal),
+            pitch: to.1.atan2(to.0.hypot(focal)),
+        }
+    }
+
+    /// screen position and scale of `p` at `depth`, None if it's behind the camera
+    pub fn project(&self, p: (f32, f32), depth: f32) -> Option<((f32, f32), f32)> {
+        let r = (p.0 - self.position.0, p.1 - self.position.1);
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let d = depth * self.focal;
+        let x = r.0 * cos_yaw - d * sin_yaw;
+        let d = r.0 * sin_yaw + d * cos_yaw;
+        let y = r.1 * cos_pitch - d * sin_pitch;
+        let d = r.1 * sin_pitch + d * cos_pitch;
+        if d < NEAR * self.focal {
+            return None;
+        }
+        let scale = self.focal / d;
+        Some(((x * scale, y * scale), scale))
+    }
+}
@@ -0,0 +1,44 @@
+use flux_map::FluxMap;
+use serde::{Serialize, Deserialize};
+
+use crate::judgement::FluxJudgement;
+
+/// how health changes per judged note, health goes from 0 to 1 and the play fails at 0
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxHealthConfig {
+    pub miss_drain: f64,
+    /// gained on a perfect, less on worse hits
+    pub hit_regen: f64,
+}
+
+impl Default for FluxHealthConfig {
+    fn default() -> Self {
+        Self {
+            miss_drain: 0.2,
+            hit_regen: 0.04,
+        }
+    }
+}
+
+impl FluxHealthConfig {
+    /// maps can set rates with "hp_drain" and "hp_regen" metadata,
+    /// or for one difficulty with "hp_drain.<difficulty>" and "hp_regen.<difficulty>"
+    pub fn for_map(&self, map: &FluxMap, difficulty: &str) -> Self {
+        let rate = |key: &str| map.get_meta_string(&format!("{}.{}", key, difficulty))
+            .or_else(|| map.get_meta_string(key))
+            .and_then(|v| v.trim().parse::<f64>().ok());
+        Self {
+            miss_drain: rate("hp_drain").unwrap_or(self.miss_drain),
+            hit_regen: rate("hp_regen").unwrap_or(self.hit_regen),
+        }
+    }
+
+    /// change in health for a judgement
+    pub fn delta(&self, judgement: FluxJudgement) -> f64 {
+        match judgement {
+            FluxJudgement::Miss => -self.miss_drain,
+            j => self.hit_regen * j.accuracy(),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Serialize, Deserialize};
+
+// every COMBO_STEP notes of combo add one to the score multiplier
+const COMBO_STEP: u32 = 25;
+const MAX_COMBO_MULTIPLIER: u64 = 4;
+
+/// hit windows in ms on either side of the note, a note can be hit until `good`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxJudgementConfig {
+    pub perfect: f64,
+    pub great: f64,
+    pub good: f64,
+}
+
+impl Default for FluxJudgementConfig {
+    fn default() -> Self {
+        Self {
+            perfect: 25.0,
+            great: 55.0,
+            good: 100.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FluxJudgement {
+    Perfect,
+    Great,
+    Good,
+    Miss,
+}
+
+impl FluxJudgement {
+    pub const ALL: [FluxJudgement; 4] = [FluxJudgement::Perfect, FluxJudgement::Great, FluxJudgement::Good, FluxJudgement::Miss];
+
+    /// judgement for a hit `error_ms` away from the note, Miss if it is outside every window
+    pub fn from_error(error_ms: f64, windows: &FluxJudgementConfig) -> Self {
+        let error = error_ms.abs();
+        if error <= windows.perfect {
+            FluxJudgement::Perfect
+        } else if error <= windows.great {
+            FluxJudgement::Great
+        } else if error <= windows.good {
+            FluxJudgement::Good
+        } else {
+            FluxJudgement::Miss
+        }
+    }
+
+    /// how much the judgement counts towards accuracy, from 0 to 1
+    pub fn accuracy(&self) -> f64 {
+        match self {
+            FluxJudgement::Perfect => 1.0,
+            FluxJudgement::Great => 0.75,
+            FluxJudgement::Good => 0.5,
+            FluxJudgement::Miss => 0.0,
+        }
+    }
+
+    pub fn base_score(&self) -> u64 {
+        match self {
+            FluxJudgement::Perfect => 300,
+            FluxJudgement::Great => 200,
+            FluxJudgement::Good => 100,
+            FluxJudgement::Miss => 0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxJudgement::Perfect => "Perfect",
+            FluxJudgement::Great => "Great",
+            FluxJudgement::Good => "Good",
+            FluxJudgement::Miss => "Miss",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+pub fn combo_multiplier(combo: u32) -> u64 {
+    (1 + (combo / COMBO_STEP) as u64).min(MAX_COMBO_MULTIPLIER)
+}
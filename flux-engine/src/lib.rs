@@ -0,0 +1,309 @@
+pub mod autoplay;
+pub mod camera;
+pub mod health;
+pub mod judgement;
+pub mod mods;
+pub mod stats;
+#[cfg(test)]
+mod tests;
+
+use flux_map::{FluxMap, replay::{FluxReplay, FluxReplayFrame}};
+use thiserror::Error;
+
+use health::FluxHealthConfig;
+use judgement::{FluxJudgement, FluxJudgementConfig};
+use mods::{FluxMod, FluxMods};
+use stats::FluxStats;
+
+// health at or below this is empty, drains don't add up exactly in floats
+const EMPTY_HEALTH: f64 = 1e-9;
+
+/// ms a note takes to travel the approach distance `ad` at approach rate `ar`, in units a second
+pub fn approach_time_ms(ar: f32, ad: f32) -> f64 {
+    if ar > 0.0 { ad as f64 * 1000.0 / ar as f64 } else { 0.0 }
+}
+
+/// Gameplay values the engine needs, everything else in the game config is presentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxEngineConfig {
+    /// ms from spawning to the note's time
+    pub approach_time: f64,
+    /// approach distance, notes are `z` from `ad` down to 0 at their time, when they're on the hit plane
+    pub ad: f32,
+    pub hitbox: f32,
+    pub speed: f64,
+    pub judgement: FluxJudgementConfig,
+    pub health: FluxHealthConfig,
+    pub mods: FluxMods,
+}
+
+impl FluxEngineConfig {
+    pub fn from_replay(replay: &FluxReplay) -> Self {
+        let settings = &replay.settings;
+        Self {
+            approach_time: approach_time_ms(settings.ar, settings.ad),
+            ad: settings.ad,
+            hitbox: settings.hitbox,
+            speed: settings.speed,
+            judgement: FluxJudgementConfig {
+                perfect: settings.windows[0],
+                great: settings.windows[1],
+                good: settings.windows[2],
+            },
+            health: FluxHealthConfig {
+                miss_drain: settings.health[0],
+                hit_regen: settings.health[1],
+            },
+            mods: FluxMods::from_ids(&replay.mods),
+        }
+    }
+}
+
+/// depth of the hit plane in hit plane distances, the cursor is drawn on it and notes `z` behind it
+pub const HIT_PLANE: f32 = 1.0;
+
+/// center of a note on the hit plane for map coordinates `x`,`y`.
+/// cursor positions are relative to the play area, (0,0) is the center and ±0.5 the edges
+pub fn note_position(x: f32, y: f32) -> (f32, f32) {
+    ((-x + 1.0) / 3.0, (y - 1.0) / 3.0)
+}
+
+/// A note while it is being played.
+/// Positions are in map units, 0 to 2 from edge to edge of the grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxEngineNote {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// index of the note in its difficulty
+    pub index: u32,
+    pub ms: f64,
+    pub spawn_time: f64,
+    hitsound_played: bool,
+    /// latest time before the note that the cursor was on it
+    early_error: Option<f64>,
+}
+
+impl FluxEngineNote {
+    /// whether the cursor at `x`,`y` is inside the note's hitbox on the hit plane
+    /// how far away the note is drawn, on the hit plane at the note's time
+    pub fn depth(&self) -> f32 {
+        HIT_PLANE + self.z
+    }
+
+    pub fn contains(&self, x: f32, y: f32, hitbox: f32) -> bool {
+        let (cx, cy) = note_position(self.x, self.y);
+        let half = hitbox / 3.0 / 2.0;
+        (x - cx).abs() <= half && (y - cy).abs() <= half
+    }
+
+    /// the error and judgement once the note is done, None while it can still be hit.
+    /// being on the note a bit early counts once the note reaches the hit plane
+    fn judge(&mut self, now: f64, over_note: bool, windows: &FluxJudgementConfig) -> Option<(Option<f64>, FluxJudgement)> {
+        let error = now - self.ms;
+        let early = self.early_error.map(|e| (Some(e), FluxJudgement::from_error(e, windows)));
+        if error > windows.good {
+            return early.or(Some((None, FluxJudgement::Miss)));
+        }
+        if error < -windows.good {
+            return None;
+        }
+        if over_note && error < 0.0 {
+            self.early_error = Some(error);
+            return None;
+        }
+        if over_note {
+            return Some((Some(error), FluxJudgement::from_error(error, windows)));
+        }
+        if error >= 0.0 {
+            return early;
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FluxNoteEvent {
+    /// a note reached the hit plane
+    Hitsound { index: u32 },
+    /// a note was hit or missed and removed, `x`,`y` and `ms` are the note's
+    Judged { index: u32, judgement: FluxJudgement, x: f32, y: f32, ms: f64 },
+}
+
+/// Spawns, moves and judges the notes of one difficulty against cursor input.
+/// Knows nothing about rendering, audio or the real clock, the caller steps it with song time.
+#[derive(Clone, Debug)]
+pub struct FluxEngine {
+    /// the config with the mods applied
+    pub config: FluxEngineConfig,
+    notes: Vec<FluxEngineNote>,
+    active: Vec<FluxEngineNote>,
+    index: usize,
+    failed: bool,
+}
+
+impl FluxEngine {
+    pub fn new(mut config: FluxEngineConfig, notes: &[flux_map::FluxNote]) -> Self {
+        config.hitbox = config.mods.hitbox(config.hitbox);
+        config.approach_time = config.mods.approach_time(config.approach_time);
+        config.health.miss_drain = config.mods.miss_drain(config.health.miss_drain);
+        let notes = notes.iter().enumerate().map(|(i, note)| {
+            let ms = note.time as f64;
+            let (x, y) = config.mods.note_position(note.x, note.y);
+            FluxEngineNote {
+                x,
+                y,
+                z: config.ad,
+                index: i as u32,
+                ms,
+                spawn_time: ms - (config.approach_time * config.speed),
+                hitsound_played: false,
+                early_error: None,
+            }
+        }).collect();
+        Self {
+            config,
+            notes,
+            active: vec![],
+            index: 0,
+            failed: false,
+        }
+    }
+
+    /// an engine without notes
+    pub fn empty() -> Self {
+        Self::new(FluxEngineConfig {
+            approach_time: 0.0,
+            ad: 0.0,
+            hitbox: 0.0,
+            speed: 1.0,
+            judgement: FluxJudgementConfig::default(),
+            health: FluxHealthConfig::default(),
+            mods: FluxMods::default(),
+        }, &[])
+    }
+
+    /// notes that have spawned and aren't judged yet, in spawn order
+    pub fn active_notes(&self) -> &[FluxEngineNote] {
+        &self.active
+    }
+
+    /// how many notes have spawned so far
+    pub fn spawned(&self) -> usize {
+        self.index
+    }
+
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// true once every note has been spawned and judged, or the play was failed
+    pub fn finished(&self) -> bool {
+        self.failed || (self.index >= self.notes.len() && self.active.is_empty())
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// jumps to `now` as if the notes before it never existed,
+    /// notes that would already be on screen are spawned fresh
+    pub fn seek(&mut self, now: f64) {
+        self.failed = false;
+        self.index = self.notes.iter().take_while(|n| n.spawn_time <= now).count();
+        self.active = self.notes[..self.index].iter()
+            .filter(|n| n.ms >= now)
+            .cloned()
+            .collect();
+    }
+
+    /// changes the playback speed, notes that haven't spawned yet spawn for the new speed
+    pub fn set_speed(&mut self, speed: f64) {
+        self.config.speed = speed;
+        let lead = self.config.approach_time * speed;
+        for note in self.notes[self.index..].iter_mut() {
+            note.spawn_time = note.ms - lead;
+        }
+    }
+
+    /// moves every note whose spawn time has passed into the active list
+    fn spawn_notes(&mut self, now: f64) {
+        while self.index < self.notes.len() && now >= self.notes[self.index].spawn_time {
+            self.active.push(self.notes[self.index].clone());
+            self.index += 1;
+        }
+    }
+
+    /// advances every note to `now` and judges them against the cursor at `x`,`y`,
+    /// returns what happened in note order so the caller can play sounds and switch sets
+    pub fn step(&mut self, now: f64, x: f32, y: f32, stats: &mut FluxStats) -> Vec<FluxNoteEvent> {
+        if self.failed {
+            return vec![];
+        }
+        self.spawn_notes(now);
+
+        let config = &self.config;
+        let multiplier = config.mods.score_multiplier();
+        let failed = &mut self.failed;
+        let mut events = vec![];
+        self.active.retain_mut(|note| {
+            // notes spawn `approach_time * speed` map ms early, so they always spawn at `ad`
+            let lead = config.approach_time * config.speed;
+            note.z = if lead > 0.0 { ((note.ms - now) / lead) as f32 * config.ad } else { 0.0 };
+            if note.z <= 0.0 && !note.hitsound_played {
+                events.push(FluxNoteEvent::Hitsound { index: note.index });
+                note.hitsound_played = true;
+            }
+
+            let over_note = note.contains(x, y, config.hitbox);
+            match note.judge(now, over_note, &config.judgement) {
+                Some((error, judgement)) => {
+                    stats.record(note.index, note.ms, error, judgement, multiplier);
+                    stats.health = (stats.health + config.health.delta(judgement)).clamp(0.0, 1.0);
+                    if judgement == FluxJudgement::Miss && config.mods.fails_on_miss() {
+                        stats.health = 0.0;
+                    }
+                    if stats.health <= EMPTY_HEALTH && !config.mods.has(FluxMod::NoFail) {
+                        stats.health = 0.0;
+                        *failed = true;
+                        stats.failed = true;
+                    }
+                    events.push(FluxNoteEvent::Judged { index: note.index, judgement, x: note.x, y: note.y, ms: note.ms });
+                    false
+                }
+                None => true,
+            }
+        });
+        events
+    }
+
+    /// plays a whole stream of timed cursor inputs and returns the resulting stats
+    pub fn simulate(config: FluxEngineConfig, notes: &[flux_map::FluxNote], inputs: &[FluxReplayFrame]) -> FluxStats {
+        let mut engine = Self::new(config, notes);
+        let mut stats = FluxStats::new();
+        for input in inputs {
+            if engine.failed {
+                break;
+            }
+            engine.step(input.ms, input.x, input.y, &mut stats);
+        }
+        stats
+    }
+
+    /// replays `replay` on `map`, the stats are what the player got if the replay is genuine
+    pub fn simulate_replay(map: &FluxMap, replay: &FluxReplay) -> Result<FluxStats, FluxEngineError> {
+        if map.content_hash() != replay.map_hash {
+            return Err(FluxEngineError::WrongMap);
+        }
+        let notes = map.difficulties.get(&replay.difficulty).ok_or(FluxEngineError::MissingDifficulty(replay.difficulty.clone()))?;
+        Ok(Self::simulate(FluxEngineConfig::from_replay(replay), notes, &replay.frames))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FluxEngineError {
+    #[error("the replay was recorded on a different map")]
+    WrongMap,
+    #[error("missing difficulty '{0}'")]
+    MissingDifficulty(String),
+}
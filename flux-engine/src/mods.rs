@@ -0,0 +1,165 @@
+use serde::{Serialize, Deserialize};
+
+/// gameplay modifiers picked before a play
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FluxMod {
+    MirrorX,
+    MirrorY,
+    /// notes fade out before reaching the hit plane
+    Hidden,
+    /// notes only fade in shortly before the hit plane
+    Ghost,
+    /// the play can't be failed
+    NoFail,
+    /// the first miss fails the play
+    SuddenDeath,
+    /// smaller hitbox, faster notes and more health drain
+    HardRock,
+}
+
+// hard rock changes
+const HARD_ROCK_HITBOX: f32 = 0.75;
+const HARD_ROCK_APPROACH: f64 = 0.75;
+const HARD_ROCK_DRAIN: f64 = 1.5;
+
+impl FluxMod {
+    pub const ALL: [FluxMod; 7] = [FluxMod::MirrorX, FluxMod::MirrorY, FluxMod::Hidden, FluxMod::Ghost, FluxMod::NoFail, FluxMod::SuddenDeath, FluxMod::HardRock];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxMod::MirrorX => "Mirror X",
+            FluxMod::MirrorY => "Mirror Y",
+            FluxMod::Hidden => "Hidden",
+            FluxMod::Ghost => "Ghost",
+            FluxMod::NoFail => "No Fail",
+            FluxMod::SuddenDeath => "Sudden Death",
+            FluxMod::HardRock => "Hard Rock",
+        }
+    }
+
+    /// name stored in replays and results
+    pub fn id(&self) -> &'static str {
+        match self {
+            FluxMod::MirrorX => "mirror_x",
+            FluxMod::MirrorY => "mirror_y",
+            FluxMod::Hidden => "hidden",
+            FluxMod::Ghost => "ghost",
+            FluxMod::NoFail => "no_fail",
+            FluxMod::SuddenDeath => "sudden_death",
+            FluxMod::HardRock => "hard_rock",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.id() == id)
+    }
+
+    pub fn score_multiplier(&self) -> f64 {
+        match self {
+            FluxMod::MirrorX | FluxMod::MirrorY | FluxMod::SuddenDeath => 1.0,
+            FluxMod::Hidden | FluxMod::Ghost => 1.06,
+            FluxMod::NoFail => 0.5,
+            FluxMod::HardRock => 1.1,
+        }
+    }
+
+    /// mods that can't be enabled at the same time as this one
+    pub fn conflicts(&self) -> &'static [FluxMod] {
+        match self {
+            FluxMod::NoFail => &[FluxMod::SuddenDeath],
+            FluxMod::SuddenDeath => &[FluxMod::NoFail],
+            FluxMod::Hidden => &[FluxMod::Ghost],
+            FluxMod::Ghost => &[FluxMod::Hidden],
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FluxMods {
+    enabled: Vec<FluxMod>,
+}
+
+impl FluxMods {
+    /// mods from their ids, unknown ids are ignored
+    pub fn from_ids(ids: &[String]) -> Self {
+        let mut mods = Self::default();
+        for m in ids.iter().filter_map(|id| FluxMod::from_id(id)) {
+            mods.set(m, true);
+        }
+        mods
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.enabled.iter().map(|m| m.id().to_string()).collect()
+    }
+
+    pub fn has(&self, m: FluxMod) -> bool {
+        self.enabled.contains(&m)
+    }
+
+    /// enabling a mod turns off the ones it conflicts with
+    pub fn set(&mut self, m: FluxMod, enabled: bool) {
+        self.enabled.retain(|e| *e != m && (!enabled || !m.conflicts().contains(e)));
+        if enabled {
+            self.enabled.push(m);
+            self.enabled.sort_by_key(|e| FluxMod::ALL.iter().position(|a| a == e));
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FluxMod> {
+        self.enabled.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.enabled.is_empty()
+    }
+
+    pub fn score_multiplier(&self) -> f64 {
+        self.enabled.iter().map(|m| m.score_multiplier()).product()
+    }
+
+    /// short list of the enabled mods for display
+    pub fn names(&self) -> String {
+        self.enabled.iter().map(|m| m.name()).collect::<Vec<&str>>().join(", ")
+    }
+
+    /// note position in map coordinates after mirroring
+    pub fn note_position(&self, x: f32, y: f32) -> (f32, f32) {
+        let x = if self.has(FluxMod::MirrorX) { 2.0 - x } else { x };
+        let y = if self.has(FluxMod::MirrorY) { 2.0 - y } else { y };
+        (x, y)
+    }
+
+    pub fn hitbox(&self, hitbox: f32) -> f32 {
+        if self.has(FluxMod::HardRock) { hitbox * HARD_ROCK_HITBOX } else { hitbox }
+    }
+
+    pub fn approach_time(&self, approach_time: f64) -> f64 {
+        if self.has(FluxMod::HardRock) { approach_time * HARD_ROCK_APPROACH } else { approach_time }
+    }
+
+    pub fn miss_drain(&self, miss_drain: f64) -> f64 {
+        if self.has(FluxMod::HardRock) { miss_drain * HARD_ROCK_DRAIN } else { miss_drain }
+    }
+
+    /// opacity of a note at depth `z` out of `ad`, for hidden and ghost
+    pub fn opacity(&self, z: f32, ad: f32) -> f32 {
+        let progress = if ad > 0.0 { z / ad } else { 0.0 };
+        if self.has(FluxMod::Hidden) {
+            // gone by the last 30% of the approach
+            return ((progress - 0.3) / 0.3).clamp(0.0, 1.0);
+        }
+        if self.has(FluxMod::Ghost) {
+            // appears in the last 50% of the approach
+            return ((0.5 - progress) / 0.2).clamp(0.0, 1.0);
+        }
+        1.0
+    }
+
+    /// whether a single miss ends the play
+    pub fn fails_on_miss(&self) -> bool {
+        self.has(FluxMod::SuddenDeath) && !self.has(FluxMod::NoFail)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::judgement::{FluxJudgement, combo_multiplier};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FluxHitRecord {
+    pub note_index: u32,
+    pub note_ms: f64,
+    /// hit time minus note time in ms, None for misses
+    pub error: Option<f64>,
+    pub judgement: FluxJudgement,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FluxStats {
+    pub notes_hit: usize,
+    pub notes_missed: usize,
+    pub note_total: usize,
+    pub score: u64,
+    pub combo: u32,
+    pub max_combo: u32,
+    /// counts indexed by `FluxJudgement::index`
+    pub judgements: [usize; 4],
+    pub hits: Vec<FluxHitRecord>,
+    /// from 0 to 1, the play fails when it runs out
+    pub health: f64,
+    pub failed: bool,
+    accuracy_total: f64,
+}
+
+impl Default for FluxStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FluxStats {
+    pub fn new() -> Self {
+        Self {
+            notes_hit: 0,
+            notes_missed: 0,
+            note_total: 0,
+            score: 0,
+            combo: 0,
+            max_combo: 0,
+            judgements: [0; 4],
+            hits: vec![],
+            health: 1.0,
+            failed: false,
+            accuracy_total: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// `multiplier` is the score multiplier of the enabled mods
+    pub fn record(&mut self, note_index: u32, note_ms: f64, error: Option<f64>, judgement: FluxJudgement, multiplier: f64) {
+        if judgement == FluxJudgement::Miss {
+            self.notes_missed += 1;
+            self.combo = 0;
+        } else {
+            self.notes_hit += 1;
+            self.combo += 1;
+            self.max_combo = self.max_combo.max(self.combo);
+        }
+        self.score += ((judgement.base_score() * combo_multiplier(self.combo)) as f64 * multiplier).round() as u64;
+        self.accuracy_total += judgement.accuracy();
+        self.judgements[judgement.index()] += 1;
+        self.note_total += 1;
+        self.hits.push(FluxHitRecord { note_index, note_ms, error, judgement });
+    }
+
+    /// weighted accuracy of the notes judged so far in percent
+    pub fn accuracy(&self) -> f64 {
+        if self.note_total == 0 {
+            return 100.0;
+        }
+        self.accuracy_total / self.note_total as f64 * 100.0
+    }
+
+    /// average hit error in ms, negative is early
+    pub fn mean_error(&self) -> f64 {
+        let errors: Vec<f64> = self.hits.iter().filter_map(|h| h.error).collect();
+        if errors.is_empty() {
+            return 0.0;
+        }
+        errors.iter().sum::<f64>() / errors.len() as f64
+    }
+}
@@ -0,0 +1,329 @@
+use flux_map::{FluxMap, FluxNote, replay::{FluxReplay, FluxReplayFrame}};
+
+use crate::{health::FluxHealthConfig, FluxEngine, HIT_PLANE, camera::FluxProjection, FluxEngineConfig, FluxNoteEvent, approach_time_ms, autoplay::{FluxAutoplay, FluxEasing}, judgement::{FluxJudgement, FluxJudgementConfig}, mods::{FluxMod, FluxMods}, stats::FluxStats};
+
+const AWAY: (f32, f32) = (-10.0, -10.0);
+
+fn config() -> FluxEngineConfig {
+    FluxEngineConfig {
+        approach_time: 500.0,
+        ad: 6.0,
+        hitbox: 1.14,
+        speed: 1.0,
+        judgement: FluxJudgementConfig::default(),
+        health: FluxHealthConfig::default(),
+        mods: FluxMods::default(),
+    }
+}
+
+fn note_pos(x: f32, y: f32) -> (f32, f32) {
+    crate::note_position(x, y)
+}
+
+/// cursor inputs every `frame_ms` from `start` to `end`, with the cursor at `path(t)`
+fn inputs(start: f64, end: f64, frame_ms: f64, path: impl Fn(f64) -> (f32, f32)) -> Vec<FluxReplayFrame> {
+    let mut frames = vec![];
+    let mut t = start;
+    while t <= end {
+        let (x, y) = path(t);
+        frames.push(FluxReplayFrame { ms: t, x, y });
+        t += frame_ms;
+    }
+    frames
+}
+
+#[test]
+fn stacked_notes_are_hit_in_one_frame() {
+    let notes: Vec<_> = (0..3).map(|_| FluxNote::new(1000, 1.0, 1.0)).collect();
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+    let (x, y) = note_pos(1.0, 1.0);
+
+    engine.step(900.0, AWAY.0, AWAY.1, &mut stats);
+    let events = engine.step(1000.0, x, y, &mut stats);
+
+    let judged = events.iter().filter(|e| matches!(e, FluxNoteEvent::Judged { .. })).count();
+    assert_eq!(judged, 3);
+    assert_eq!(stats.judgements[FluxJudgement::Perfect.index()], 3);
+    assert_eq!(stats.combo, 3);
+    assert!(engine.finished());
+}
+
+#[test]
+fn long_frame_misses_every_passed_note() {
+    let notes: Vec<_> = (0..5).map(|i| FluxNote::new(1000 + i * 10, 0.0, 0.0)).collect();
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+
+    engine.step(0.0, AWAY.0, AWAY.1, &mut stats);
+    engine.step(2000.0, AWAY.0, AWAY.1, &mut stats);
+
+    assert_eq!(stats.notes_missed, 5);
+    assert_eq!(stats.note_total, 5);
+    assert!(stats.hits.iter().all(|h| h.error.is_none()));
+    assert!(engine.finished());
+}
+
+#[test]
+fn judged_events_carry_the_note() {
+    let notes = vec![FluxNote::new(1000, 2.0, 0.5)];
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+
+    engine.step(0.0, AWAY.0, AWAY.1, &mut stats);
+    let events = engine.step(2000.0, AWAY.0, AWAY.1, &mut stats);
+
+    assert_eq!(events.last(), Some(&FluxNoteEvent::Judged { index: 0, judgement: FluxJudgement::Miss, x: 2.0, y: 0.5, ms: 1000.0 }));
+}
+
+#[test]
+fn final_note_is_spawned_and_judged() {
+    let notes = vec![FluxNote::new(600, 2.0, 0.0)];
+    let on_note = note_pos(2.0, 0.0);
+
+    let stats = FluxEngine::simulate(config(), &notes, &inputs(0.0, 800.0, 16.0, |_| on_note));
+
+    assert_eq!(stats.notes_hit, 1);
+}
+
+#[test]
+fn scripted_path_hits_every_note() {
+    let notes = vec![
+        FluxNote::new(1000, 0.0, 0.0),
+        FluxNote::new(1200, 2.0, 0.0),
+        FluxNote::new(1400, 2.0, 2.0),
+        FluxNote::new(1600, 0.0, 2.0),
+        FluxNote::new(1800, 1.0, 1.0),
+    ];
+    let targets: Vec<(f64, (f32, f32))> = notes.iter().map(|n| (n.time as f64, note_pos(n.x, n.y))).collect();
+    // linearly moves between notes, arriving on each one at its time
+    let path = |t: f64| {
+        let next = targets.iter().position(|(ms, _)| *ms >= t).unwrap_or(targets.len() - 1);
+        if next == 0 {
+            return targets[0].1;
+        }
+        let (ms_a, a) = targets[next - 1];
+        let (ms_b, b) = targets[next];
+        let f = ((t - ms_a) / (ms_b - ms_a)).clamp(0.0, 1.0) as f32;
+        (a.0 + (b.0 - a.0) * f, a.1 + (b.1 - a.1) * f)
+    };
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+    let mut events = vec![];
+    for input in inputs(0.0, 2200.0, 8.0, path) {
+        events.extend(engine.step(input.ms, input.x, input.y, &mut stats));
+    }
+
+    assert_eq!(stats.notes_hit, 5);
+    assert_eq!(stats.notes_missed, 0);
+    assert_eq!(stats.max_combo, 5);
+    assert!(stats.hits.iter().all(|h| h.error.unwrap().abs() <= config().judgement.good));
+    let hitsounds = events.iter().filter(|e| matches!(e, FluxNoteEvent::Hitsound { .. })).count();
+    assert_eq!(hitsounds, 5);
+    assert!(engine.finished());
+}
+
+#[test]
+fn leaving_early_keeps_the_early_hit() {
+    let notes = vec![FluxNote::new(1000, 0.0, 0.0)];
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+    let (x, y) = note_pos(0.0, 0.0);
+
+    engine.step(980.0, x, y, &mut stats);
+    assert_eq!(stats.note_total, 0);
+    engine.step(1010.0, AWAY.0, AWAY.1, &mut stats);
+
+    assert_eq!(stats.hits[0].error, Some(-20.0));
+    assert_eq!(stats.hits[0].judgement, FluxJudgement::Perfect);
+}
+
+#[test]
+fn replay_gives_the_same_stats() {
+    let mut map = FluxMap::new();
+    map.add_difficulty("default".to_string(), vec![FluxNote::new(500, 0.0, 0.0), FluxNote::new(700, 2.0, 2.0), FluxNote::new(900, 1.0, 1.0)]);
+    map.add_music(vec![1, 2, 3]);
+    let settings = flux_map::replay::FluxReplaySettings { ar: 10.0, ad: 6.0, hitbox: 1.14, speed: 1.0, windows: [25.0, 55.0, 100.0], health: [0.2, 0.04] };
+    let mut replay = FluxReplay::new(map.content_hash(), "default".to_string(), settings);
+    // hits the first note late and never reaches the second
+    let (x, y) = note_pos(0.0, 0.0);
+    for i in 0..100 {
+        replay.add_frame(i as f64 * 12.0, x, y);
+    }
+
+    let played = FluxEngine::simulate_replay(&map, &replay).unwrap();
+    let watched = FluxEngine::simulate_replay(&map, &FluxReplay::parse_data(&replay.to_bytes()).unwrap()).unwrap();
+    assert_eq!(played, watched);
+    assert_eq!(played.notes_hit, 1);
+    assert_eq!(played.notes_missed, 2);
+
+    replay.difficulty = "hard".to_string();
+    assert!(FluxEngine::simulate_replay(&map, &replay).is_err());
+}
+
+#[test]
+fn approach_time_is_in_ms() {
+    assert_eq!(approach_time_ms(10.0, 6.0), 600.0);
+    assert_eq!(approach_time_ms(0.0, 6.0), 0.0);
+
+    let settings = flux_map::replay::FluxReplaySettings { ar: 20.0, ad: 5.0, hitbox: 1.14, speed: 1.0, windows: [25.0, 55.0, 100.0], health: [0.2, 0.04] };
+    let replay = FluxReplay::new([0; 32], "default".to_string(), settings);
+    assert_eq!(FluxEngineConfig::from_replay(&replay).approach_time, 250.0);
+}
+
+#[test]
+fn notes_spawn_at_the_approach_distance_at_any_speed() {
+    for speed in [0.75, 1.5] {
+        let notes = vec![FluxNote::new(1000, 0.0, 0.0)];
+        let mut engine = FluxEngine::new(FluxEngineConfig { speed, ..config() }, &notes);
+        let mut stats = FluxStats::new();
+
+        engine.step(1000.0 - 500.0 * speed, AWAY.0, AWAY.1, &mut stats);
+
+        assert_eq!(engine.active_notes()[0].z, 6.0);
+    }
+}
+
+#[test]
+fn notes_reach_the_hit_plane_at_their_time() {
+    let notes = vec![FluxNote::new(1000, 0.0, 0.0)];
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+
+    let early = engine.step(950.0, AWAY.0, AWAY.1, &mut stats);
+    assert!(early.is_empty());
+    assert!(engine.active_notes()[0].depth() > HIT_PLANE);
+
+    let events = engine.step(1000.0, AWAY.0, AWAY.1, &mut stats);
+    assert_eq!(engine.active_notes()[0].z, 0.0);
+    assert_eq!(engine.active_notes()[0].depth(), HIT_PLANE);
+    assert_eq!(events, vec![FluxNoteEvent::Hitsound { index: 0 }]);
+}
+
+#[test]
+fn a_note_is_drawn_under_the_cursor_at_its_time() {
+    let play_area = (600.0, 600.0);
+    let notes = vec![FluxNote::new(1000, 0.2, 1.7)];
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+    engine.step(1000.0, AWAY.0, AWAY.1, &mut stats);
+    let note = engine.active_notes()[0].clone();
+
+    // the cursor right over the note, with the camera turned away from it
+    let (x, y) = note_pos(note.x, note.y);
+    assert!(note.contains(x, y, config().hitbox));
+    let cursor = (x * play_area.0, y * play_area.1);
+    let camera = FluxProjection::look_at(700.0, (cursor.0 * 0.3, cursor.1 * 0.3), (-100.0, 50.0));
+
+    let (on_screen, _) = camera.project(cursor, note.depth()).unwrap();
+    let (under_cursor, _) = camera.project(cursor, HIT_PLANE).unwrap();
+    assert!((on_screen.0 - under_cursor.0).abs() < 1e-3 && (on_screen.1 - under_cursor.1).abs() < 1e-3);
+}
+
+#[test]
+fn zero_approach_time_keeps_notes_on_the_hit_plane() {
+    let notes = vec![FluxNote::new(1000, 0.0, 0.0)];
+    let mut engine = FluxEngine::new(FluxEngineConfig { approach_time: 0.0, ..config() }, &notes);
+    let mut stats = FluxStats::new();
+
+    let events = engine.step(1000.0, AWAY.0, AWAY.1, &mut stats);
+
+    assert_eq!(engine.active_notes()[0].z, 0.0);
+    assert_eq!(events, vec![FluxNoteEvent::Hitsound { index: 0 }]);
+}
+
+#[test]
+fn autoplay_hits_every_note() {
+    // jumps across the grid, a stack and notes closer together than a frame
+    let notes = vec![
+        FluxNote::new(1000, 0.0, 0.0),
+        FluxNote::new(1150, 2.0, 2.0),
+        FluxNote::new(1150, 2.0, 2.0),
+        FluxNote::new(1300, 0.0, 2.0),
+        FluxNote::new(1305, 2.0, 0.0),
+        FluxNote::new(1500, 1.0, 1.0),
+    ];
+    for easing in FluxEasing::ALL {
+        assert_eq!(easing.ease(0.0), 0.0);
+        assert!((easing.ease(1.0) - 1.0).abs() < 1e-6);
+        let autoplay = FluxAutoplay::new(&notes, easing, &FluxMods::default());
+        let stats = FluxEngine::simulate(config(), &notes, &inputs(0.0, 2000.0, 1000.0 / 144.0, |t| autoplay.cursor_at(t)));
+        assert_eq!(stats.notes_hit, notes.len(), "{:?}", easing);
+        assert_eq!(stats.notes_missed, 0);
+    }
+}
+
+#[test]
+fn mods() {
+    let mut mods = FluxMods::default();
+    mods.set(FluxMod::NoFail, true);
+    mods.set(FluxMod::MirrorX, true);
+    mods.set(FluxMod::SuddenDeath, true);
+    // sudden death replaces no fail
+    assert!(!mods.has(FluxMod::NoFail));
+    assert_eq!(FluxMods::from_ids(&mods.ids()), mods);
+    assert_eq!(mods.ids(), vec!["mirror_x", "sudden_death"]);
+
+    // mirrored notes are where autoplay goes, and the first miss ends the play
+    let notes = vec![FluxNote::new(1000, 0.0, 0.0), FluxNote::new(1200, 0.0, 2.0), FluxNote::new(1400, 2.0, 2.0)];
+    let mut config = config();
+    config.mods = mods.clone();
+    let autoplay = FluxAutoplay::new(&notes, FluxEasing::Linear, &mods);
+    let stats = FluxEngine::simulate(config.clone(), &notes, &inputs(0.0, 2000.0, 8.0, |t| autoplay.cursor_at(t)));
+    assert_eq!(stats.notes_hit, 3);
+    let stats = FluxEngine::simulate(config.clone(), &notes, &inputs(0.0, 2000.0, 8.0, |t| if t < 1100.0 { autoplay.cursor_at(t) } else { AWAY }));
+    assert_eq!((stats.notes_hit, stats.notes_missed), (1, 1));
+
+    // hard rock shrinks the hitbox
+    let mut config = self::config();
+    config.mods.set(FluxMod::HardRock, true);
+    assert!(FluxEngine::new(config, &notes).config.hitbox < self::config().hitbox);
+    assert!(FluxMods::from_ids(&["hard_rock".to_string(), "no_fail".to_string()]).score_multiplier() < 1.0);
+}
+
+#[test]
+fn health_drains_until_failed() {
+    let notes: Vec<_> = (0..10).map(|i| FluxNote::new(1000 + i * 200, 1.0, 1.0)).collect();
+    let on_note = note_pos(1.0, 1.0);
+    // hits the first two notes and then leaves
+    let path = |t: f64| if t < 1300.0 { on_note } else { AWAY };
+
+    let stats = FluxEngine::simulate(config(), &notes, &inputs(0.0, 4000.0, 8.0, path));
+    assert!(stats.failed);
+    assert_eq!(stats.health, 0.0);
+    // full health after the hits, then five misses of 0.2
+    assert_eq!(stats.notes_missed, 5);
+
+    let mut no_fail = config();
+    no_fail.mods.set(FluxMod::NoFail, true);
+    let stats = FluxEngine::simulate(no_fail, &notes, &inputs(0.0, 4000.0, 8.0, path));
+    assert!(!stats.failed);
+    assert_eq!(stats.notes_missed, 8);
+
+    let mut map = FluxMap::new();
+    map.add_metadata("hp_drain.hard".to_string(), b"0.5".to_vec());
+    assert_eq!(FluxHealthConfig::default().for_map(&map, "hard").miss_drain, 0.5);
+    assert_eq!(FluxHealthConfig::default().for_map(&map, "easy"), FluxHealthConfig::default());
+}
+
+#[test]
+fn seek_skips_passed_notes_and_respawns_later_ones() {
+    let notes: Vec<_> = (0..4).map(|i| FluxNote::new(1000 + i * 1000, 1.0, 1.0)).collect();
+    let mut engine = FluxEngine::new(config(), &notes);
+    let mut stats = FluxStats::new();
+    let (x, y) = note_pos(1.0, 1.0);
+
+    engine.step(5000.0, AWAY.0, AWAY.1, &mut stats);
+    assert!(engine.finished());
+
+    // the third note is 300ms away and already on screen
+    engine.seek(2700.0);
+    assert_eq!(engine.active_notes().iter().map(|n| n.index).collect::<Vec<_>>(), vec![2]);
+    let mut stats = FluxStats::new();
+    for input in inputs(2700.0, 4200.0, 10.0, |_| (x, y)) {
+        engine.step(input.ms, input.x, input.y, &mut stats);
+    }
+    assert!(engine.finished());
+    assert_eq!(stats.notes_hit, 2);
+    assert_eq!(stats.note_total, 2);
+}
@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand, Args, ValueEnum};
-use flux_map::{FluxMap, convert::{sspmv1::SSPM1, sspm::SSPM, fluxlegacy::FluxLegacy}};
+use flux_map::{FluxMap, convert::{sspmv1::SSPM1, sspm::SSPM, fluxlegacy::FluxLegacy, osz::Osz}, pack::FluxMapPack};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -17,7 +17,11 @@ enum Commands {
     /// create a map with provided data
     Create(SingleCreate),
     /// convert a map from another format
-    Convert(SingleConvert)
+    Convert(SingleConvert),
+    /// bundle multiple maps into a map pack
+    Pack(PackCreate),
+    /// extract all maps from a map pack into a folder
+    Unpack(PackExtract),
 }
 #[derive(Args)]
 struct SingleCreate {
@@ -54,6 +58,29 @@ struct SingleConvert {
     out_path : PathBuf,
 
 }
+#[derive(Args)]
+struct PackCreate {
+    /// name of the pack
+    #[arg(short,long)]
+    name : String,
+    /// name of the pack author
+    #[arg(short,long)]
+    author : String,
+    /// description of the pack
+    #[arg(short,long,default_value="")]
+    description : String,
+    out_path : PathBuf,
+    /// maps to add, in order
+    #[arg(required=true)]
+    maps : Vec<PathBuf>,
+}
+#[derive(Args)]
+struct PackExtract {
+    /// pack to read from
+    in_path : PathBuf,
+    /// folder to write the maps to
+    out_path : PathBuf,
+}
 
 #[derive(ValueEnum,Debug,Clone,Eq,PartialEq, PartialOrd, Ord)]
 enum MapFormat {
@@ -61,6 +88,8 @@ enum MapFormat {
     FluxLegacy,
     /// SSPM format (Sound Space Plus)
     SSPM,
+    /// osz format (osu! beatmap archive)
+    Osz,
 }
 fn main() {
     let gargs : CliArguments = CliArguments::parse();
@@ -106,8 +135,26 @@ fn main() {
                 MapFormat::SSPM => {
                     SSPM::try_from(fdata.as_slice()).expect("unable to parse data").into()
                 }
+                MapFormat::Osz => {
+                    Osz::try_from(fdata.as_slice()).expect("unable to parse data").into()
+                }
             };
             flux.save(args.out_path);
         }
+        Commands::Pack(args) => {
+            let mut pack = FluxMapPack::new(args.name, args.author, args.description);
+            for path in args.maps {
+                let map = FluxMap::open(path.clone()).expect("unable to parse map");
+                let fname = path.file_name().expect("map path has no file name").to_string_lossy().to_string();
+                pack.add_map(fname, map);
+            }
+            pack.save(args.out_path).expect("unable to write pack");
+        }
+        Commands::Unpack(args) => {
+            let pack = FluxMapPack::open(args.in_path).expect("unable to parse pack");
+            for path in pack.unpack(args.out_path).expect("unable to unpack maps") {
+                println!("unpacked {:?}", path);
+            }
+        }
     }
 }
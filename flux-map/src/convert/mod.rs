@@ -1,4 +1,40 @@
 pub mod fluxlegacy;
+pub mod osz;
 pub mod sspm;
 pub mod sspmv2;
 pub mod sspmv1;
+
+use thiserror::Error;
+
+use crate::{FluxMap, FluxMapError};
+
+use self::{fluxlegacy::{FluxLegacy, FluxLegacyError}, osz::{Osz, OszError}, sspm::{SSPM, MapParseError}};
+
+/// converts map data to a flux map based on the file extension it came from
+/// (`flux`, `fluxl`, `sspm` or `osz`).
+pub fn convert_data(data:&[u8], ext:&str) -> Result<FluxMap,ConvertError> {
+    match ext.to_lowercase().as_str() {
+        "flux" => Ok(FluxMap::parse_data(data)?),
+        "fluxl" => Ok(FluxLegacy::try_from(data)?.into()),
+        "sspm" => match SSPM::try_from(data)? {
+            SSPM::V2 => Err(ConvertError::Sspm(MapParseError::V2)),
+            sspm => Ok(sspm.into()),
+        },
+        "osz" => Ok(Osz::try_from(data)?.into()),
+        _ => Err(ConvertError::UnknownFormat(ext.to_string())),
+    }
+}
+
+#[derive(Debug,Error)]
+pub enum ConvertError {
+    #[error("{0}")]
+    Flux(#[from] FluxMapError),
+    #[error("{0}")]
+    Legacy(#[from] FluxLegacyError),
+    #[error("{0}")]
+    Sspm(#[from] MapParseError),
+    #[error("{0}")]
+    Osz(#[from] OszError),
+    #[error("Unknown map format '{0}'")]
+    UnknownFormat(String),
+}
@@ -0,0 +1,168 @@
+use std::io::{Cursor, Read};
+
+use thiserror::Error;
+use zip::ZipArchive;
+
+use crate::{FluxMap, FluxNote};
+
+// osu! playfield size in osu!pixels
+const OSU_WIDTH : f32 = 512.0;
+const OSU_HEIGHT : f32 = 384.0;
+// hit object type bit for spinners, they have no position so they are skipped
+const OSU_SPINNER : u32 = 1 << 3;
+
+/// an osu! beatmap archive, every `.osu` file inside becomes a difficulty.
+pub struct Osz {
+    pub title : String,
+    pub artist : String,
+    pub creator : String,
+    pub difficulties : Vec<(String,Vec<FluxNote>)>,
+    pub music_data : Vec<u8>,
+    pub image_data : Option<Vec<u8>>,
+}
+/// the parts of a single `.osu` file flux cares about
+struct OsuBeatmap {
+    audio_file : String,
+    background : Option<String>,
+    title : String,
+    artist : String,
+    creator : String,
+    version : String,
+    notes : Vec<FluxNote>,
+}
+
+impl OsuBeatmap {
+    fn parse(data:&str) -> Self {
+        let mut map = OsuBeatmap {
+            audio_file : String::new(),
+            background : None,
+            title : String::new(),
+            artist : String::new(),
+            creator : String::new(),
+            version : String::new(),
+            notes : Vec::new(),
+        };
+        let mut section = "";
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = &line[1..line.len()-1];
+                continue;
+            }
+            match section {
+                "General" | "Metadata" => {
+                    if let Some((k,v)) = line.split_once(':') {
+                        let v = v.trim().to_string();
+                        match k.trim() {
+                            "AudioFilename" => map.audio_file = v,
+                            "Title" => map.title = v,
+                            "Artist" => map.artist = v,
+                            "Creator" => map.creator = v,
+                            "Version" => map.version = v,
+                            _ => {}
+                        }
+                    }
+                }
+                "Events" => {
+                    // background: 0,0,"bg.jpg",0,0
+                    let ev = line.split(',').collect::<Vec<&str>>();
+                    if map.background.is_none() && ev.len() >= 3 && ev[0] == "0" {
+                        map.background = Some(ev[2].trim_matches('"').to_string());
+                    }
+                }
+                "HitObjects" => {
+                    // x,y,time,type,...
+                    let ho = line.split(',').collect::<Vec<&str>>();
+                    if ho.len() < 4 {
+                        continue;
+                    }
+                    let (Ok(x),Ok(y),Ok(time),Ok(kind)) = (ho[0].parse::<f32>(),ho[1].parse::<f32>(),ho[2].parse::<f32>(),ho[3].parse::<u32>()) else {
+                        continue;
+                    };
+                    if kind & OSU_SPINNER != 0 || time < 0.0 {
+                        continue;
+                    }
+                    // flux notes go from 0 to 2 on both axes with x and y flipped compared to osu!
+                    let x = 2.0 - (x / OSU_WIDTH).clamp(0.0, 1.0) * 2.0;
+                    let y = 2.0 - (y / OSU_HEIGHT).clamp(0.0, 1.0) * 2.0;
+                    map.notes.push(FluxNote::new(time as u32, x, y));
+                }
+                _ => {}
+            }
+        }
+        map.notes.sort_by_key(|n| n.time);
+        map
+    }
+}
+
+fn read_entry(archive:&mut ZipArchive<Cursor<&[u8]>>, name:&str) -> Option<Vec<u8>> {
+    // osu! treats file names case insensitively
+    let name = archive.file_names().find(|n| n.eq_ignore_ascii_case(name))?.to_string();
+    let mut file = archive.by_name(&name).ok()?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+impl TryFrom<&[u8]> for Osz {
+    type Error = OszError;
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut archive = ZipArchive::new(Cursor::new(data)).or(Err(OszError::BadArchive))?;
+        let mut beatmaps = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).or(Err(OszError::BadArchive))?;
+            if !file.name().to_lowercase().ends_with(".osu") {
+                continue;
+            }
+            let mut osu = String::new();
+            file.read_to_string(&mut osu).or(Err(OszError::BadBeatmap(file.name().to_string())))?;
+            beatmaps.push(OsuBeatmap::parse(&osu));
+        }
+        let first = beatmaps.first().ok_or(OszError::NoBeatmaps)?;
+        let music_data = read_entry(&mut archive, &first.audio_file).ok_or(OszError::NoAudio(first.audio_file.clone()))?;
+        let image_data = first.background.as_ref().and_then(|bg| read_entry(&mut archive, bg));
+        let (title, artist, creator) = (first.title.clone(), first.artist.clone(), first.creator.clone());
+        let difficulties = beatmaps.into_iter().map(|b| (b.version, b.notes)).collect();
+        Ok(Self {
+            title,
+            artist,
+            creator,
+            difficulties,
+            music_data,
+            image_data,
+        })
+    }
+}
+
+impl From<Osz> for FluxMap {
+    fn from(osz: Osz) -> Self {
+        let mut m = FluxMap::new();
+        m.add_metadata("mapper".to_string(), osz.creator.as_bytes().to_vec());
+        m.add_metadata("artist".to_string(), osz.artist.as_bytes().to_vec());
+        m.add_metadata("song_name".to_string(), osz.title.as_bytes().to_vec());
+        for (name,notes) in osz.difficulties {
+            let name = if name.is_empty() { "default".to_string() } else { name };
+            m.add_difficulty(name, notes);
+        }
+        m.add_music(osz.music_data);
+        if let Some(x) = osz.image_data {
+            m.add_image(x);
+        }
+        m
+    }
+}
+
+#[derive(Debug,Error)]
+pub enum OszError {
+    #[error("not a valid osz archive")]
+    BadArchive,
+    #[error("bad beatmap '{0}'")]
+    BadBeatmap(String),
+    #[error("archive has no beatmaps")]
+    NoBeatmaps,
+    #[error("missing audio file '{0}'")]
+    NoAudio(String),
+}
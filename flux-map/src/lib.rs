@@ -1,8 +1,11 @@
 pub mod tests;
 pub mod convert;
-use std::{path::PathBuf, io::Cursor, collections::HashMap};
+pub mod pack;
+pub mod replay;
+use std::{path::PathBuf, io::{Cursor, Read, Seek, Write, BufReader}, collections::HashMap, fs::File};
 
 use binrw::{BinWriterExt, BinReaderExt, binrw, VecArgs};
+use sha2::{Sha256, Digest};
 use thiserror::Error;
 #[derive(Debug)]
 pub struct FluxMap {
@@ -29,6 +32,11 @@ impl FluxNote {
 }
 const FLUX_SIG : [u8;4] = [b'F',b'L',b'U',b'X'];
 
+/// a `FluxMap::content_hash` as lowercase hex
+pub fn hash_hex(hash:&[u8;32]) -> String {
+    hash.iter().map(|b| format!("{:02x}",b)).collect()
+}
+
 
 #[binrw]
 #[br(big)]
@@ -72,12 +80,81 @@ impl FluxMap {
     pub fn add_image(&mut self,data:Vec<u8>) {
         self.image_data = Some(data);
     }
+    /// metadata value as a string, invalid utf-8 is replaced
+    pub fn get_meta_string(&self,key:&str) -> Option<String> {
+        self.meta.get(key).map(|v| String::from_utf8_lossy(v).to_string())
+    }
+    /// sha256 over the map contents, independent of how the file was laid out
+    pub fn content_hash(&self) -> [u8;32] {
+        let mut hasher = self.info_hasher();
+        hasher.update((self.music_data.len() as u32).to_be_bytes());
+        hasher.update(&self.music_data);
+        hasher.finalize().into()
+    }
+    /// `content_hash` up to the music
+    fn info_hasher(&self) -> Sha256 {
+        let mut hasher = Sha256::new();
+        let mut keys : Vec<&String> = self.meta.keys().collect();
+        keys.sort();
+        for k in keys {
+            hasher.update((k.len() as u16).to_be_bytes());
+            hasher.update(k.as_bytes());
+            hasher.update((self.meta[k].len() as u32).to_be_bytes());
+            hasher.update(&self.meta[k]);
+        }
+        let mut keys : Vec<&String> = self.difficulties.keys().collect();
+        keys.sort();
+        for k in keys {
+            hasher.update((k.len() as u16).to_be_bytes());
+            hasher.update(k.as_bytes());
+            hasher.update((self.difficulties[k].len() as u64).to_be_bytes());
+            for note in &self.difficulties[k] {
+                hasher.update(note.time.to_be_bytes());
+                hasher.update(note.x.to_be_bytes());
+                hasher.update(note.y.to_be_bytes());
+            }
+        }
+        let image = self.image_data.as_deref().unwrap_or_default();
+        hasher.update((image.len() as u32).to_be_bytes());
+        hasher.update(image);
+        hasher
+    }
+    /// `content_hash` as lowercase hex
+    pub fn content_hash_hex(&self) -> String {
+        hash_hex(&self.content_hash())
+    }
     pub fn open(path_from: PathBuf) -> Result<Self,FluxMapError> {
-        let all_data = std::fs::read(path_from).unwrap();
+        let all_data = std::fs::read(path_from)?;
         Self::parse_data(&all_data)
     }
+    /// like `open` but stops before the music, `music_data` is left empty.
+    /// enough to list a map without reading its audio
+    pub fn open_info(path_from: PathBuf) -> Result<Self,FluxMapError> {
+        let mut r = BufReader::new(File::open(path_from)?);
+        Self::read_info(&mut r)
+    }
+    /// `open_info` and the `content_hash` of the whole map, the music is hashed as it's read and not kept
+    pub fn open_info_hashed(path_from: PathBuf) -> Result<(Self,[u8;32]),FluxMapError> {
+        let mut r = BufReader::new(File::open(path_from)?);
+        let map = Self::read_info(&mut r)?;
+        let music_len = r.read_be::<u32>().map_err(|_| FluxMapError::BadFormat(FluxBadFormatType::BadMusic))?;
+        let mut hasher = map.info_hasher();
+        hasher.update(music_len.to_be_bytes());
+        let hashed = std::io::copy(&mut r.take(music_len as u64), &mut hasher)?;
+        if hashed != music_len as u64 {
+            return Err(FluxMapError::BadFormat(FluxBadFormatType::BadMusic));
+        }
+        Ok((map, hasher.finalize().into()))
+    }
     pub fn parse_data(data:&[u8]) -> Result<Self,FluxMapError> {
         let mut r = Cursor::new(data);
+        let mut map = Self::read_info(&mut r)?;
+        let music = r.read_be::<SizedVec>().map_err(|_| FluxMapError::BadFormat(FluxBadFormatType::BadMusic))?;
+        map.music_data = music.data;
+        Ok(map)
+    }
+    /// everything up to the music
+    fn read_info<R: Read + Seek>(r: &mut R) -> Result<Self,FluxMapError> {
         let sig = r.read_be::<[u8;4]>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadSignature)))?;
         if sig != FLUX_SIG {
             return Err(FluxMapError::BadFormat(FluxBadFormatType::BadSignature));
@@ -85,40 +162,19 @@ impl FluxMap {
         let version = r.read_be::<u8>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadVersion)))?;
         match version {
             1 => {
-                let mut meta = HashMap::new();
-                let meta_count = r.read_be::<u16>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMetadata)))?;
-                for _ in 0..meta_count {
-                    let key : SizedString = r.read_be().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMetadata)))?;
-                    let value : SizedVec = r.read_be().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMetadata)))?;
-                    meta.insert(key.to_string(),value.data);
-                }
-                let mut difficulties = HashMap::new();
-                let difficulty_count = r.read_be::<u16>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
-                for _ in 0..difficulty_count {
-                    let key : SizedString = r.read_be().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
-                    let note_count = r.read_be::<u64>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
-                    let mut notes = Vec::with_capacity(note_count as usize);
-                    for _ in 0..note_count {
-                        let time = r.read_be::<u32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
-                        let x = r.read_be::<f32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
-                        let y = r.read_be::<f32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
-                        let note = FluxNote::new(time,x,y);
-                        notes.push(note);
-                    }
-                    difficulties.insert(key.to_string(),notes);
-                }
+                let meta = read_meta(r)?;
+                let difficulties = read_difficulties(r)?;
                 let image_len = r.read_be::<u32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadImage)))?;
                 let image_data = if image_len == 0 {
                     None
                 } else {
-                    Some(r.read_be_args(VecArgs::builder().count(image_len as usize).finalize()).unwrap())
+                    Some(r.read_be_args(VecArgs::builder().count(image_len as usize).finalize()).map_err(|_| FluxMapError::BadFormat(FluxBadFormatType::BadImage))?)
                 };
-                let music = r.read_be::<SizedVec>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMusic)))?;
                 Ok(Self {
                     version,
                     meta,
                     difficulties,
-                    music_data: music.data,
+                    music_data: Vec::new(),
                     image_data,
                 })
             },
@@ -126,6 +182,9 @@ impl FluxMap {
         }
     }
     pub fn save(self,path_to: PathBuf) {
+        std::fs::write(&path_to, self.to_bytes()).expect("Failed to write flm file. SHOULD NOT HAPPEN???");
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut flm_data = Vec::<u8>::with_capacity(
             self.music_data.len() 
             + self.difficulties.values().fold(0, |x,f| x+ f.len()) 
@@ -135,28 +194,8 @@ impl FluxMap {
         let mut w = Cursor::new(&mut flm_data);
         w.write_be(&FLUX_SIG).unwrap();
         w.write_be(&self.version).unwrap();
-        // meta key count
-        w.write_be(&(self.meta.len() as u16)).unwrap();
-        // write metadata
-        for (k,v) in self.meta {
-            w.write_be(&(k.len() as u16)).unwrap();
-            w.write_be(&k.as_bytes()).unwrap();
-            w.write_be(&(v.len() as u32)).unwrap();
-            w.write_be(&v).unwrap();
-        }
-        // difficulty count
-        w.write_be(&(self.difficulties.len() as u16)).unwrap();
-        // write difficulties
-        for (k,v) in self.difficulties {
-            w.write_be(&(k.len() as u16)).unwrap();
-            w.write_be(&k.as_bytes()).unwrap();
-            w.write_be(&(v.len() as u64)).unwrap();
-            for note in v {
-                w.write_be(&note.time).unwrap();
-                w.write_be(&note.x).unwrap();
-                w.write_be(&note.y).unwrap();
-            }
-        }
+        write_meta(&mut w, &self.meta);
+        write_difficulties(&mut w, &self.difficulties);
         // write image data        
         if let Some(image_data) = self.image_data.as_ref() {
             w.write_be(&(image_data.len() as u32)).unwrap();
@@ -167,8 +206,30 @@ impl FluxMap {
         // write music data
         w.write_be(&(self.music_data.len() as u32)).unwrap();
         w.write_be(&self.music_data).unwrap();
-        std::fs::write(&path_to, flm_data).expect("Failed to write flm file. SHOULD NOT HAPPEN???");
-    
+        flm_data
+    }
+    /// length of a difficulty in ms, the time of its last note
+    pub fn length_ms(notes:&[FluxNote]) -> u32 {
+        notes.iter().map(|n| n.time).max().unwrap_or(0)
+    }
+    /// rough difficulty estimate of a difficulty from note density and how fast the cursor has to travel
+    pub fn star_rating(notes:&[FluxNote]) -> f32 {
+        if notes.len() < 2 {
+            return 0.0;
+        }
+        let mut speeds : Vec<f32> = notes.windows(2).map(|w| {
+            let dist = ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt();
+            // stacks and jumps closer than 20ms are treated as 20ms apart
+            let dt = (w[1].time.saturating_sub(w[0].time)).max(20) as f32 / 1000.0;
+            dist / dt
+        }).collect();
+        speeds.sort_by(|a,b| b.total_cmp(a));
+        // the hardest 10% of the map decides most of the rating
+        let peak_count = (speeds.len() / 10).max(1);
+        let peak = speeds[..peak_count].iter().sum::<f32>() / peak_count as f32;
+        let span = (Self::length_ms(notes) - notes.iter().map(|n| n.time).min().unwrap_or(0)).max(1000) as f32 / 1000.0;
+        let nps = notes.len() as f32 / span;
+        peak.sqrt() * 1.2 + nps.sqrt() * 0.8
     }
     pub fn convert_ss_to_flux(ssmap:&Vec<u8>) -> Vec<FluxNote> {
         let as_str = std::str::from_utf8(&ssmap).unwrap();
@@ -184,6 +245,60 @@ impl FluxMap {
         notes
     }
 }
+pub(crate) fn read_meta<R: Read + Seek>(r: &mut R) -> Result<HashMap<String,Vec<u8>>,FluxMapError> {
+    let mut meta = HashMap::new();
+    let meta_count = r.read_be::<u16>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMetadata)))?;
+    for _ in 0..meta_count {
+        let key : SizedString = r.read_be().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMetadata)))?;
+        let value : SizedVec = r.read_be().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadMetadata)))?;
+        meta.insert(key.to_string(),value.data);
+    }
+    Ok(meta)
+}
+pub(crate) fn read_difficulties<R: Read + Seek>(r: &mut R) -> Result<HashMap<String,Vec<FluxNote>>,FluxMapError> {
+    let mut difficulties = HashMap::new();
+    let difficulty_count = r.read_be::<u16>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
+    for _ in 0..difficulty_count {
+        let key : SizedString = r.read_be().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
+        let note_count = r.read_be::<u64>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
+        let mut notes = Vec::with_capacity(note_count as usize);
+        for _ in 0..note_count {
+            let time = r.read_be::<u32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
+            let x = r.read_be::<f32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
+            let y = r.read_be::<f32>().or_else(|_| Err(FluxMapError::BadFormat(FluxBadFormatType::BadDifficulty)))?;
+            let note = FluxNote::new(time,x,y);
+            notes.push(note);
+        }
+        difficulties.insert(key.to_string(),notes);
+    }
+    Ok(difficulties)
+}
+pub(crate) fn write_meta<W: Write + Seek>(w: &mut W, meta: &HashMap<String,Vec<u8>>) {
+    // meta key count
+    w.write_be(&(meta.len() as u16)).unwrap();
+    // write metadata
+    for (k,v) in meta {
+        w.write_be(&(k.len() as u16)).unwrap();
+        w.write_be(&k.as_bytes()).unwrap();
+        w.write_be(&(v.len() as u32)).unwrap();
+        w.write_be(v).unwrap();
+    }
+}
+pub(crate) fn write_difficulties<W: Write + Seek>(w: &mut W, difficulties: &HashMap<String,Vec<FluxNote>>) {
+    // difficulty count
+    w.write_be(&(difficulties.len() as u16)).unwrap();
+    // write difficulties
+    for (k,v) in difficulties {
+        w.write_be(&(k.len() as u16)).unwrap();
+        w.write_be(&k.as_bytes()).unwrap();
+        w.write_be(&(v.len() as u64)).unwrap();
+        for note in v {
+            w.write_be(&note.time).unwrap();
+            w.write_be(&note.x).unwrap();
+            w.write_be(&note.y).unwrap();
+        }
+    }
+}
 impl TryFrom<&[u8]> for FluxMap {
     type Error = FluxMapError;
     fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
@@ -196,6 +311,8 @@ impl TryFrom<&[u8]> for FluxMap {
 pub enum FluxMapError {
     #[error("Bad Format {0}")]
     BadFormat(FluxBadFormatType),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
 }
 #[derive(Debug,Error)]
 pub enum FluxBadFormatType {
@@ -0,0 +1,203 @@
+use std::{path::{PathBuf, Path}, io::Cursor, collections::HashMap, ffi::OsStr};
+
+use binrw::{BinReaderExt, BinWriterExt};
+use sha2::{Sha256, Digest};
+use thiserror::Error;
+
+use crate::{FluxMap, FluxMapError, SizedString, SizedVec, read_meta, read_difficulties, write_meta, write_difficulties};
+
+const PACK_SIG : [u8;4] = [b'F',b'X',b'P',b'K'];
+/// blob index used when a map has no image
+const NO_BLOB : u32 = u32::MAX;
+
+/// `dir/stem.flux`, or `dir/stem (2).flux` and so on if that file already exists
+pub fn free_map_path(dir:&Path,stem:&OsStr) -> PathBuf {
+    let mut path = dir.join(stem).with_extension("flux");
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{} ({})", stem.to_string_lossy(), n)).with_extension("flux");
+        n += 1;
+    }
+    path
+}
+
+/// `len` as a u16 length prefix, too long values can't be stored
+fn len_u16(what:&'static str,len:usize) -> Result<u16,FluxPackError> {
+    u16::try_from(len).or(Err(FluxPackError::TooLong(what, len)))
+}
+
+/// A named, ordered collection of maps.
+/// Identical music and images are only stored once in the file.
+pub struct FluxMapPack {
+    pub version:u8,
+    pub name:String,
+    pub author:String,
+    pub description:String,
+    pub maps:Vec<FluxPackEntry>,
+}
+pub struct FluxPackEntry {
+    /// file name the map is unpacked to
+    pub file_name:String,
+    pub map:FluxMap,
+}
+
+impl FluxMapPack {
+    pub fn new(name:String,author:String,description:String) -> Self {
+        Self {
+            version:1,
+            name,
+            author,
+            description,
+            maps:Vec::new(),
+        }
+    }
+    pub fn add_map(&mut self,file_name:String,map:FluxMap) {
+        self.maps.push(FluxPackEntry { file_name, map });
+    }
+    pub fn open(path_from: PathBuf) -> Result<Self,FluxPackError> {
+        let all_data = std::fs::read(path_from)?;
+        Self::parse_data(&all_data)
+    }
+    pub fn parse_data(data:&[u8]) -> Result<Self,FluxPackError> {
+        let mut r = Cursor::new(data);
+        let sig = r.read_be::<[u8;4]>().or(Err(FluxPackError::BadFormat(r.position())))?;
+        if sig != PACK_SIG {
+            return Err(FluxPackError::UnknownSig(sig.to_vec()));
+        }
+        let version = r.read_be::<u8>().or(Err(FluxPackError::BadFormat(r.position())))?;
+        match version {
+            1 => {
+                let name : SizedString = r.read_be().or(Err(FluxPackError::BadFormat(r.position())))?;
+                let author : SizedString = r.read_be().or(Err(FluxPackError::BadFormat(r.position())))?;
+                let description : SizedVec = r.read_be().or(Err(FluxPackError::BadFormat(r.position())))?;
+                let blob_count = r.read_be::<u32>().or(Err(FluxPackError::BadFormat(r.position())))?;
+                let mut blobs = Vec::with_capacity(blob_count as usize);
+                for _ in 0..blob_count {
+                    let blob : SizedVec = r.read_be().or(Err(FluxPackError::BadFormat(r.position())))?;
+                    blobs.push(blob.data);
+                }
+                let map_count = r.read_be::<u16>().or(Err(FluxPackError::BadFormat(r.position())))?;
+                let mut maps = Vec::with_capacity(map_count as usize);
+                for _ in 0..map_count {
+                    let file_name : SizedString = r.read_be().or(Err(FluxPackError::BadFormat(r.position())))?;
+                    let map_version = r.read_be::<u8>().or(Err(FluxPackError::BadFormat(r.position())))?;
+                    let meta = read_meta(&mut r)?;
+                    let difficulties = read_difficulties(&mut r)?;
+                    let image = r.read_be::<u32>().or(Err(FluxPackError::BadFormat(r.position())))?;
+                    let music = r.read_be::<u32>().or(Err(FluxPackError::BadFormat(r.position())))?;
+                    let image_data = if image == NO_BLOB {
+                        None
+                    } else {
+                        Some(blobs.get(image as usize).ok_or(FluxPackError::MissingBlob(image))?.clone())
+                    };
+                    let music_data = blobs.get(music as usize).ok_or(FluxPackError::MissingBlob(music))?.clone();
+                    maps.push(FluxPackEntry {
+                        file_name: file_name.to_string(),
+                        map: FluxMap {
+                            version: map_version,
+                            meta,
+                            difficulties,
+                            music_data,
+                            image_data,
+                        },
+                    });
+                }
+                Ok(Self {
+                    version,
+                    name: name.to_string(),
+                    author: author.to_string(),
+                    description: String::from_utf8_lossy(&description.data).to_string(),
+                    maps,
+                })
+            },
+            _ => Err(FluxPackError::UnknownVer(version)),
+        }
+    }
+    pub fn to_bytes(&self) -> Result<Vec<u8>,FluxPackError> {
+        // dedupe music and images by content hash
+        let mut blobs : Vec<&Vec<u8>> = Vec::new();
+        let mut blob_index : HashMap<[u8;32],u32> = HashMap::new();
+        let mut map_blobs : Vec<[u32;2]> = Vec::with_capacity(self.maps.len());
+        for entry in &self.maps {
+            // [image, music]
+            let mut ids = [NO_BLOB;2];
+            for (i,data) in [entry.map.image_data.as_ref(), Some(&entry.map.music_data)].into_iter().enumerate() {
+                if let Some(data) = data {
+                    let hash : [u8;32] = Sha256::digest(data).into();
+                    ids[i] = *blob_index.entry(hash).or_insert_with(|| {
+                        blobs.push(data);
+                        (blobs.len() - 1) as u32
+                    });
+                }
+            }
+            map_blobs.push(ids);
+        }
+
+        let mut pack_data = Vec::<u8>::with_capacity(blobs.iter().fold(0, |x,b| x + b.len()) + 64);
+        let mut w = Cursor::new(&mut pack_data);
+        w.write_be(&PACK_SIG).unwrap();
+        w.write_be(&self.version).unwrap();
+        w.write_be(&len_u16("pack name", self.name.len())?).unwrap();
+        w.write_be(&self.name.as_bytes()).unwrap();
+        w.write_be(&len_u16("pack author", self.author.len())?).unwrap();
+        w.write_be(&self.author.as_bytes()).unwrap();
+        w.write_be(&(self.description.len() as u32)).unwrap();
+        w.write_be(&self.description.as_bytes()).unwrap();
+        // write shared music and image data
+        w.write_be(&(blobs.len() as u32)).unwrap();
+        for blob in blobs {
+            w.write_be(&(blob.len() as u32)).unwrap();
+            w.write_be(blob).unwrap();
+        }
+        // write maps in pack order
+        w.write_be(&len_u16("map list", self.maps.len())?).unwrap();
+        for (entry,[image,music]) in self.maps.iter().zip(map_blobs) {
+            w.write_be(&len_u16("map file name", entry.file_name.len())?).unwrap();
+            w.write_be(&entry.file_name.as_bytes()).unwrap();
+            w.write_be(&entry.map.version).unwrap();
+            write_meta(&mut w, &entry.map.meta);
+            write_difficulties(&mut w, &entry.map.difficulties);
+            w.write_be(&image).unwrap();
+            w.write_be(&music).unwrap();
+        }
+        Ok(pack_data)
+    }
+    pub fn save(&self,path_to: PathBuf) -> Result<(),FluxPackError> {
+        std::fs::write(path_to, self.to_bytes()?)?;
+        Ok(())
+    }
+    /// writes every map in the pack to `dir` as a `.flux` file and returns the written paths in pack order.
+    /// existing files are never overwritten, a map whose name is taken gets a numbered name
+    pub fn unpack(&self,dir: PathBuf) -> Result<Vec<PathBuf>,FluxPackError> {
+        std::fs::create_dir_all(&dir)?;
+        let mut paths = Vec::with_capacity(self.maps.len());
+        for entry in &self.maps {
+            // only keep the file name so a pack can't write outside of `dir`
+            let stem = Path::new(&entry.file_name).file_stem().ok_or(FluxPackError::BadFileName(entry.file_name.clone()))?;
+            let path = free_map_path(&dir, stem);
+            std::fs::write(&path, entry.map.to_bytes())?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+#[derive(Debug,Error)]
+pub enum FluxPackError {
+    #[error("Unknown signature {0:x?}")]
+    UnknownSig(Vec<u8>),
+    #[error("Unknown version '{0}'")]
+    UnknownVer(u8),
+    #[error("bad format pos: {0}")]
+    BadFormat(u64),
+    #[error("missing blob {0}")]
+    MissingBlob(u32),
+    #[error("bad map file name '{0}'")]
+    BadFileName(String),
+    #[error("{0} is too long to store ({1})")]
+    TooLong(&'static str,usize),
+    #[error("{0}")]
+    Map(#[from] FluxMapError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,160 @@
+use std::{path::PathBuf, io::Cursor};
+
+use binrw::{BinReaderExt, BinWriterExt};
+use thiserror::Error;
+
+use crate::SizedString;
+
+const REPLAY_SIG : [u8;4] = [b'F',b'X',b'R',b'P'];
+
+/// Everything needed to play a run back exactly as it happened.
+/// Cursor positions are relative to the play area, (0,0) is the center and ±0.5 the edges.
+#[derive(Debug,Clone,PartialEq)]
+pub struct FluxReplay {
+    pub version:u8,
+    /// `FluxMap::content_hash` of the played map
+    pub map_hash:[u8;32],
+    pub difficulty:String,
+    /// unix time in seconds the play ended
+    pub timestamp:u64,
+    pub settings:FluxReplaySettings,
+    pub mods:Vec<String>,
+    pub frames:Vec<FluxReplayFrame>,
+}
+/// gameplay settings the replay was played with
+#[derive(Debug,Clone,PartialEq)]
+pub struct FluxReplaySettings {
+    pub ar:f32,
+    pub ad:f32,
+    pub hitbox:f32,
+    pub speed:f64,
+    /// perfect, great and good hit windows in ms
+    pub windows:[f64;3],
+    /// health lost per miss and gained per hit, version 1 replays had no health
+    pub health:[f64;2],
+}
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct FluxReplayFrame {
+    /// song time in ms
+    pub ms:f64,
+    pub x:f32,
+    pub y:f32,
+}
+
+impl FluxReplay {
+    pub fn new(map_hash:[u8;32],difficulty:String,settings:FluxReplaySettings) -> Self {
+        Self {
+            version:2,
+            map_hash,
+            difficulty,
+            timestamp:0,
+            settings,
+            mods:Vec::new(),
+            frames:Vec::new(),
+        }
+    }
+    pub fn add_frame(&mut self,ms:f64,x:f32,y:f32) {
+        self.frames.push(FluxReplayFrame { ms, x, y });
+    }
+    pub fn open(path_from: PathBuf) -> Result<Self,FluxReplayError> {
+        let all_data = std::fs::read(path_from)?;
+        Self::parse_data(&all_data)
+    }
+    pub fn parse_data(data:&[u8]) -> Result<Self,FluxReplayError> {
+        let mut r = Cursor::new(data);
+        let sig = r.read_be::<[u8;4]>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+        if sig != REPLAY_SIG {
+            return Err(FluxReplayError::UnknownSig(sig.to_vec()));
+        }
+        let version = r.read_be::<u8>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+        match version {
+            1 | 2 => {
+                let map_hash = r.read_be::<[u8;32]>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let difficulty : SizedString = r.read_be().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let timestamp = r.read_be::<u64>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let ar = r.read_be::<f32>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let ad = r.read_be::<f32>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let hitbox = r.read_be::<f32>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let speed = r.read_be::<f64>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let windows = r.read_be::<[f64;3]>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let health = if version >= 2 {
+                    r.read_be::<[f64;2]>().or(Err(FluxReplayError::BadFormat(r.position())))?
+                } else {
+                    [0.0;2]
+                };
+                let mod_count = r.read_be::<u16>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let mut mods = Vec::with_capacity(mod_count as usize);
+                for _ in 0..mod_count {
+                    let m : SizedString = r.read_be().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                    mods.push(m.to_string());
+                }
+                let frame_count = r.read_be::<u64>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                let mut frames = Vec::with_capacity(frame_count as usize);
+                for _ in 0..frame_count {
+                    let ms = r.read_be::<f64>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                    let x = r.read_be::<f32>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                    let y = r.read_be::<f32>().or(Err(FluxReplayError::BadFormat(r.position())))?;
+                    frames.push(FluxReplayFrame { ms, x, y });
+                }
+                Ok(Self {
+                    version,
+                    map_hash,
+                    difficulty: difficulty.to_string(),
+                    timestamp,
+                    settings: FluxReplaySettings { ar, ad, hitbox, speed, windows, health },
+                    mods,
+                    frames,
+                })
+            },
+            _ => Err(FluxReplayError::UnknownVer(version)),
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut replay_data = Vec::<u8>::with_capacity(self.frames.len() * 16 + 128);
+        let mut w = Cursor::new(&mut replay_data);
+        w.write_be(&REPLAY_SIG).unwrap();
+        w.write_be(&self.version).unwrap();
+        w.write_be(&self.map_hash).unwrap();
+        w.write_be(&(self.difficulty.len() as u16)).unwrap();
+        w.write_be(&self.difficulty.as_bytes()).unwrap();
+        w.write_be(&self.timestamp).unwrap();
+        w.write_be(&self.settings.ar).unwrap();
+        w.write_be(&self.settings.ad).unwrap();
+        w.write_be(&self.settings.hitbox).unwrap();
+        w.write_be(&self.settings.speed).unwrap();
+        w.write_be(&self.settings.windows).unwrap();
+        w.write_be(&self.settings.health).unwrap();
+        w.write_be(&(self.mods.len() as u16)).unwrap();
+        for m in &self.mods {
+            w.write_be(&(m.len() as u16)).unwrap();
+            w.write_be(&m.as_bytes()).unwrap();
+        }
+        w.write_be(&(self.frames.len() as u64)).unwrap();
+        for frame in &self.frames {
+            w.write_be(&frame.ms).unwrap();
+            w.write_be(&frame.x).unwrap();
+            w.write_be(&frame.y).unwrap();
+        }
+        replay_data
+    }
+    pub fn save(&self,path_to: PathBuf) -> Result<(),FluxReplayError> {
+        std::fs::write(path_to, self.to_bytes())?;
+        Ok(())
+    }
+    /// lowercase hex of the map hash, used to name replay files
+    pub fn map_hash_hex(&self) -> String {
+        self.map_hash.iter().map(|b| format!("{:02x}",b)).collect()
+    }
+}
+
+#[derive(Debug,Error)]
+pub enum FluxReplayError {
+    #[error("Unknown signature {0:x?}")]
+    UnknownSig(Vec<u8>),
+    #[error("Unknown version '{0}'")]
+    UnknownVer(u8),
+    #[error("bad format pos: {0}")]
+    BadFormat(u64),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
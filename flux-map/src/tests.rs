@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::FluxMap;
+    use crate::{FluxMap, FluxNote, pack::{FluxMapPack, FluxPackError}, convert::convert_data, replay::{FluxReplay, FluxReplaySettings}};
 
 
     #[test]
@@ -13,4 +13,129 @@ mod tests {
         assert!(map.difficulties.len() == 1);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn pack_roundtrip() {
+        let mut pack = FluxMapPack::new("pack".to_string(), "someone".to_string(), "two maps".to_string());
+        for name in ["easy", "hard"] {
+            let mut m = FluxMap::new();
+            m.add_metadata("song_name".to_string(), name.as_bytes().to_vec());
+            m.add_difficulty("default".to_string(), vec![FluxNote::new(100, 0.0, 2.0)]);
+            m.add_music(vec![7; 4096]);
+            m.add_image(vec![3; 1024]);
+            pack.add_map(format!("{}.flux", name), m);
+        }
+        let data = pack.to_bytes().unwrap();
+        // shared music and image are only stored once
+        assert!(data.len() < 4096 * 2);
+
+        let pack = FluxMapPack::parse_data(&data).unwrap();
+        assert!(pack.name == "pack" && pack.author == "someone" && pack.description == "two maps");
+        assert!(pack.maps.len() == 2);
+        assert!(pack.maps[0].file_name == "easy.flux" && pack.maps[1].file_name == "hard.flux");
+        assert!(pack.maps[1].map.meta.get("song_name").unwrap() == b"hard");
+        assert!(pack.maps[1].map.music_data == vec![7; 4096]);
+        assert!(pack.maps[1].map.image_data == Some(vec![3; 1024]));
+        assert!(pack.maps[1].map.difficulties.get("default").unwrap()[0].time == 100);
+    }
+
+    #[test]
+    fn pack_rejects_long_names() {
+        let pack = FluxMapPack::new("a".repeat(70000), "someone".to_string(), String::new());
+        assert!(matches!(pack.to_bytes(), Err(FluxPackError::TooLong("pack name", 70000))));
+    }
+
+    #[test]
+    fn unpack_keeps_maps_with_the_same_name() {
+        let dir = std::env::temp_dir().join("flux-map-unpack-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut pack = FluxMapPack::new("pack".to_string(), "someone".to_string(), String::new());
+        for (i, name) in ["a/x.flux", "b/x.flux"].into_iter().enumerate() {
+            let mut m = FluxMap::new();
+            m.add_music(vec![i as u8; 16]);
+            pack.add_map(name.to_string(), m);
+        }
+        let paths = pack.unpack(dir.clone()).unwrap();
+        assert!(paths == vec![dir.join("x.flux"), dir.join("x (2).flux")]);
+        assert!(FluxMap::open(paths[1].clone()).unwrap().music_data == vec![1; 16]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_info_skips_the_music() {
+        let path = std::env::temp_dir().join("flux-map-open-info-test.flux");
+        let mut m = FluxMap::new();
+        m.add_metadata("song_name".to_string(), b"song".to_vec());
+        m.add_difficulty("default".to_string(), vec![FluxNote::new(100, 0.0, 2.0)]);
+        m.add_music(vec![7; 4096]);
+        m.add_image(vec![3; 1024]);
+        std::fs::write(&path, m.to_bytes()).unwrap();
+
+        let info = FluxMap::open_info(path.clone()).unwrap();
+        assert!(info.music_data.is_empty());
+        assert!(info.image_data == Some(vec![3; 1024]));
+        assert!(info.get_meta_string("song_name").unwrap() == "song");
+        assert!(info.difficulties.get("default").unwrap().len() == 1);
+        let (_, hash) = FluxMap::open_info_hashed(path.clone()).unwrap();
+        assert!(hash == m.content_hash());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn convert_osz() {
+        use std::io::Write;
+        let osu = "osu file format v14\n\n[General]\nAudioFilename: Audio.mp3\n\n[Metadata]\nTitle:song\nArtist:band\nCreator:mapper\nVersion:Insane\n\n[Events]\n0,0,\"bg.jpg\",0,0\n\n[HitObjects]\n512,0,2000,1,0\n0,384,1000,2,0,B|1:1,1,1\n256,192,3000,12,0,4000\n";
+        let mut data = std::io::Cursor::new(Vec::new());
+        {
+            let mut zip = zip::ZipWriter::new(&mut data);
+            let options = zip::write::FileOptions::default();
+            zip.start_file("song (mapper) [Insane].osu", options).unwrap();
+            zip.write_all(osu.as_bytes()).unwrap();
+            zip.start_file("audio.mp3", options).unwrap();
+            zip.write_all(&[1, 2, 3]).unwrap();
+            zip.start_file("bg.jpg", options).unwrap();
+            zip.write_all(&[4, 5]).unwrap();
+            zip.finish().unwrap();
+        }
+        let map = convert_data(data.get_ref(), "osz").unwrap();
+        assert!(map.get_meta_string("song_name").unwrap() == "song");
+        assert!(map.get_meta_string("artist").unwrap() == "band");
+        assert!(map.music_data == vec![1, 2, 3]);
+        assert!(map.image_data == Some(vec![4, 5]));
+        // spinners are skipped and notes are sorted by time
+        let notes = map.difficulties.get("Insane").unwrap();
+        assert!(notes.len() == 2);
+        assert!(notes[0].time == 1000 && notes[0].x == 2.0 && notes[0].y == 0.0);
+        assert!(notes[1].time == 2000 && notes[1].x == 0.0 && notes[1].y == 2.0);
+    }
+
+    #[test]
+    fn star_rating() {
+        let slow = vec![FluxNote::new(0, 0.0, 0.0), FluxNote::new(1000, 1.0, 0.0), FluxNote::new(2000, 1.0, 1.0)];
+        let fast = vec![FluxNote::new(0, 0.0, 0.0), FluxNote::new(100, 2.0, 0.0), FluxNote::new(200, 0.0, 2.0)];
+        assert!(FluxMap::length_ms(&slow) == 2000);
+        assert!(FluxMap::star_rating(&slow[..1]) == 0.0);
+        assert!(FluxMap::star_rating(&fast) > FluxMap::star_rating(&slow));
+    }
+
+    #[test]
+    fn replay_roundtrip() {
+        let mut map = FluxMap::new();
+        map.add_metadata("song_name".to_string(), b"song".to_vec());
+        map.add_metadata("artist".to_string(), b"band".to_vec());
+        map.add_difficulty("default".to_string(), vec![FluxNote::new(100, 0.0, 2.0)]);
+        map.add_music(vec![1, 2, 3]);
+        // the hash doesn't depend on the file layout
+        let reparsed = FluxMap::parse_data(&map.to_bytes()).unwrap();
+        assert!(reparsed.content_hash() == map.content_hash());
+
+        let settings = FluxReplaySettings { ar: 10.0, ad: 6.0, hitbox: 1.14, speed: 1.25, windows: [25.0, 55.0, 100.0], health: [0.2, 0.04] };
+        let mut replay = FluxReplay::new(map.content_hash(), "default".to_string(), settings);
+        replay.timestamp = 1700000000;
+        replay.mods.push("mirror_x".to_string());
+        replay.add_frame(0.0, 0.0, 0.0);
+        replay.add_frame(16.5, -0.25, 0.4);
+        let parsed = FluxReplay::parse_data(&replay.to_bytes()).unwrap();
+        assert!(parsed == replay);
+        assert!(parsed.map_hash_hex().len() == 64);
+    }
+}
@@ -0,0 +1,56 @@
+use flux_engine::{camera::FluxProjection, note_position, HIT_PLANE};
+use nannou::prelude::*;
+
+use super::config::{FluxCameraMode, FluxConfig};
+
+/// The camera in front of the play area, made fresh every frame from the config and the cursor.
+/// Positions are in pixels from the middle of the play area and depths in hit plane distances like
+/// `FluxEngineNote::depth`. Notes, effects and the cursor are all drawn through `FluxProjection`,
+/// the same projection the engine's tests check hits against.
+#[derive(Clone, Copy, Debug)]
+pub struct FluxCamera {
+    projection: FluxProjection,
+    play_area: Vec2,
+}
+
+impl FluxCamera {
+    /// the camera for the cursor at `cursor` on the hit plane
+    pub fn new(config: &FluxConfig, cursor: Vec2, window_height: f32) -> Self {
+        let camera = &config.camera;
+        let focal = (window_height / 2.0) / (camera.fov.clamp(1.0, 179.0).to_radians() / 2.0).tan();
+        let position = cursor * camera.parallax;
+        let target = match camera.mode {
+            FluxCameraMode::Lock => position,
+            FluxCameraMode::HalfLock => position.lerp(cursor, 0.5),
+            FluxCameraMode::Spin => cursor,
+        };
+        Self {
+            // turns so the target is in the middle of the screen
+            projection: FluxProjection::look_at(focal, position.into(), target.into()),
+            play_area: Vec2::new(config.misc.play_area_width, config.misc.play_area_height),
+        }
+    }
+
+    /// screen position and scale of `p` at `depth`, None if it's behind the camera
+    pub fn project(&self, p: Vec2, depth: f32) -> Option<(Vec2, f32)> {
+        self.projection.project(p.into(), depth).map(|(p, scale)| (p.into(), scale))
+    }
+
+    /// a `size` rect at `p` at `depth`, kept facing the screen
+    pub fn rect(&self, p: Vec2, size: Vec2, depth: f32) -> Option<Rect> {
+        self.project(p, depth).map(|(center, scale)| Rect::from_xy_wh(center, size * scale))
+    }
+
+    /// where a note at `x`,`y` in map units is drawn at `depth`, before its sprite settings
+    pub fn note_rect(&self, x: f32, y: f32, depth: f32) -> Option<Rect> {
+        let (x, y) = note_position(x, y);
+        self.rect(Vec2::new(x, y) * self.play_area, self.play_area / 3.0, depth)
+    }
+
+    /// the corners of the play area on the hit plane
+    pub fn play_area(&self) -> Option<[Vec2; 4]> {
+        let (w, h) = (self.play_area.x / 2.0, self.play_area.y / 2.0);
+        let corner = |x: f32, y: f32| self.project(Vec2::new(x, y), HIT_PLANE).map(|(p, _)| p);
+        Some([corner(-w, h)?, corner(w, h)?, corner(w, -h)?, corner(-w, -h)?])
+    }
+}
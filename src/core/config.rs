@@ -1,46 +1,407 @@
+use std::path::Path;
 
-#[derive(Clone)]
+use flux_engine::{FluxEngineConfig, approach_time_ms, autoplay::FluxEasing, health::FluxHealthConfig, judgement::FluxJudgementConfig, mods::FluxMods};
+use serde::{Serialize, Deserialize};
+
+use crate::sets::manifest::FluxColor;
+
+use super::history::sibling;
+
+/// what the approach speed is set by, the approach distance is set either way
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FluxApproachMode {
+    /// approach rate in units a second
+    #[default]
+    Rate,
+    /// ms from spawning to the note's time
+    Time,
+}
+
+impl FluxApproachMode {
+    pub const ALL: [FluxApproachMode; 2] = [FluxApproachMode::Rate, FluxApproachMode::Time];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxApproachMode::Rate => "AR",
+            FluxApproachMode::Time => "Time",
+        }
+    }
+}
+
+/// how note opacity rises over the fade in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FluxOpacityCurve {
+    #[default]
+    Linear,
+    /// slow at first
+    EaseIn,
+    /// fast at first
+    EaseOut,
+    Smooth,
+}
+
+impl FluxOpacityCurve {
+    pub const ALL: [FluxOpacityCurve; 4] = [FluxOpacityCurve::Linear, FluxOpacityCurve::EaseIn, FluxOpacityCurve::EaseOut, FluxOpacityCurve::Smooth];
+
+    /// opacity for fade in progress `t` from 0 to 1
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            FluxOpacityCurve::Linear => t,
+            FluxOpacityCurve::EaseIn => t * t,
+            FluxOpacityCurve::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            FluxOpacityCurve::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxOpacityCurve::Linear => "Linear",
+            FluxOpacityCurve::EaseIn => "Ease in",
+            FluxOpacityCurve::EaseOut => "Ease out",
+            FluxOpacityCurve::Smooth => "Smooth",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FluxNoteConfig {
+    pub approach: FluxApproachMode,
     pub ar: f32, // approach rate
     pub ad: f32, // approach distance
-    pub fs: u32, // fade steps TODO: Implement fade
+    /// ms from spawning to the note's time in the time approach mode
+    pub approach_ms: f32,
+    /// how far from the spawn notes take to fade in, 0 shows them right away
+    pub fade_in: f32,
+    pub opacity_curve: FluxOpacityCurve,
+    /// ms missed notes stay on the hit plane, notes also stop at the hit plane instead of flying past it. 0 turns it off
+    pub pushback: f32,
     pub hitbox: f32,
-    pub approach_time: f32,
+    /// note colors in turn, used over the map's and the noteset's when not empty
+    pub colors: Vec<FluxColor>,
+    /// use the colors a map suggests in its "colors" metadata
+    pub map_colors: bool,
 }
 
-#[derive(Clone)]
+/// how the mouse moves the cursor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FluxCursorMode {
+    /// raw mouse motion times the sensitivity, the system cursor is grabbed
+    #[default]
+    Relative,
+    /// a region of the window maps onto the play area, for tablets
+    Absolute,
+}
+
+impl FluxCursorMode {
+    pub const ALL: [FluxCursorMode; 2] = [FluxCursorMode::Relative, FluxCursorMode::Absolute];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxCursorMode::Relative => "Relative",
+            FluxCursorMode::Absolute => "Absolute",
+        }
+    }
+}
+
+/// part of the window that maps onto the play area in absolute mode,
+/// in fractions of the window from the top left
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxCursorRegion {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FluxCursorConfig {
+    /// cursor pixels per count of mouse motion in relative mode
     pub sens: f32,
     pub size: f32,
+    /// how far past the play area edges the cursor can go
     pub edge_buffer: f32,
+    pub mode: FluxCursorMode,
+    pub region: FluxCursorRegion,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FluxAudioConfig {
     pub volume: f64,
     pub speed: f64,
     pub offset: i64,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FluxSetsConfig {
-    pub hit: &'static str,
-    pub cursor: &'static str,
-    pub note: &'static str,
+    pub hit: String,
+    pub cursor: String,
+    pub note: String,
+    /// skin directory or zip name in the skins directory, empty for none
+    pub skin: String,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FluxMiscConfig {
     pub debug: bool,
     pub play_area_width: f32,
     pub play_area_height: f32,
+    /// how the autoplay cursor moves between notes
+    pub autoplay_easing: FluxEasing,
+}
+
+/// where the camera looks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FluxCameraMode {
+    /// straight at the play area
+    #[default]
+    Lock,
+    /// halfway towards the cursor
+    HalfLock,
+    /// at the cursor, which stays in the middle of the screen
+    Spin,
+}
+
+impl FluxCameraMode {
+    pub const ALL: [FluxCameraMode; 3] = [FluxCameraMode::Lock, FluxCameraMode::HalfLock, FluxCameraMode::Spin];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FluxCameraMode::Lock => "Lock",
+            FluxCameraMode::HalfLock => "Half-lock",
+            FluxCameraMode::Spin => "Spin",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxCameraConfig {
+    pub mode: FluxCameraMode,
+    /// how far the camera moves with the cursor, 0 stays put and 1 follows it
+    pub parallax: f32,
+    /// vertical field of view in degrees
+    pub fov: f32,
+}
+
+/// which hit effects are shown, the skin decides how they look
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxEffectsConfig {
+    pub hit: bool,
+    pub miss: bool,
+    /// judgement text over judged notes
+    pub popups: bool,
+    /// leaves out popups for perfect hits
+    pub hide_perfect_popups: bool,
+    pub combo_pulse: bool,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct FluxConfig {
     pub note: FluxNoteConfig,
     pub cursor: FluxCursorConfig,
     pub audio: FluxAudioConfig,
     pub sets: FluxSetsConfig,
     pub misc: FluxMiscConfig,
+    pub camera: FluxCameraConfig,
+    pub effects: FluxEffectsConfig,
+    pub judgement: FluxJudgementConfig,
+    /// maps can override these per difficulty
+    pub health: FluxHealthConfig,
+    /// mods picked in the map menu
+    pub mods: FluxMods,
+    /// set when a broken config file couldn't be moved aside, so it isn't written over
+    #[serde(skip)]
+    read_only: bool,
+}
+
+impl Default for FluxNoteConfig {
+    fn default() -> Self {
+        Self {
+            approach: FluxApproachMode::Rate,
+            ar: 10.0,
+            ad: 6.0,
+            approach_ms: 600.0,
+            fade_in: 0.0,
+            opacity_curve: FluxOpacityCurve::Linear,
+            pushback: 0.0,
+            hitbox: 1.14,
+            colors: vec![],
+            map_colors: true,
+        }
+    }
+}
+
+impl Default for FluxCursorConfig {
+    fn default() -> Self {
+        Self {
+            sens: 0.9,
+            size: 70.0,
+            edge_buffer: 20.0,
+            mode: FluxCursorMode::Relative,
+            region: FluxCursorRegion::default(),
+        }
+    }
+}
+
+impl Default for FluxCursorRegion {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            w: 1.0,
+            h: 1.0,
+        }
+    }
+}
+
+impl Default for FluxAudioConfig {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            speed: 1.0,
+            offset: 0,
+        }
+    }
+}
+
+impl Default for FluxSetsConfig {
+    fn default() -> Self {
+        Self {
+            note: String::from("rounded"),
+            cursor: String::from("default"),
+            hit: String::from("thump"),
+            skin: String::new(),
+        }
+    }
+}
+
+impl Default for FluxMiscConfig {
+    fn default() -> Self {
+        Self {
+            play_area_width: 0.0,
+            play_area_height: 0.0,
+            debug: false,
+            autoplay_easing: FluxEasing::Cubic,
+        }
+    }
+}
+
+impl Default for FluxCameraConfig {
+    fn default() -> Self {
+        Self {
+            mode: FluxCameraMode::Lock,
+            parallax: 0.0,
+            fov: 70.0,
+        }
+    }
+}
+
+impl Default for FluxEffectsConfig {
+    fn default() -> Self {
+        Self {
+            hit: true,
+            miss: true,
+            popups: false,
+            hide_perfect_popups: true,
+            combo_pulse: true,
+        }
+    }
+}
+
+impl FluxNoteConfig {
+    /// ms from spawning to the note's time
+    pub fn approach_time(&self) -> f64 {
+        match self.approach {
+            FluxApproachMode::Rate => approach_time_ms(self.ar, self.ad),
+            FluxApproachMode::Time => self.approach_ms as f64,
+        }
+    }
+
+    /// the approach rate that gives the approach time, replays store this
+    pub fn approach_rate(&self) -> f32 {
+        match self.approach {
+            FluxApproachMode::Rate => self.ar,
+            FluxApproachMode::Time => self.ad * 1000.0 / self.approach_ms.max(1.0),
+        }
+    }
+
+    /// opacity of a note `z` away from the hit plane from fading in
+    pub fn fade_opacity(&self, z: f32) -> f32 {
+        if self.fade_in <= 0.0 {
+            return 1.0;
+        }
+        self.opacity_curve.apply((self.ad - z) / self.fade_in)
+    }
+}
+
+impl FluxConfig {
+    /// the gameplay part of the config the engine judges notes with
+    pub fn engine(&self) -> FluxEngineConfig {
+        FluxEngineConfig {
+            approach_time: self.note.approach_time(),
+            ad: self.note.ad,
+            hitbox: self.note.hitbox,
+            speed: self.audio.speed,
+            judgement: self.judgement.clone(),
+            health: self.health.clone(),
+            mods: self.mods.clone(),
+        }
+    }
+
+    /// loads the config from a toml file, missing fields fall back to the defaults.
+    /// a file that doesn't parse is moved aside so it isn't lost
+    pub fn load(path: &Path) -> Self {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(_) => {
+                log::warn!("No config at {:?}, using defaults", path);
+                return Self::default();
+            }
+        };
+        match toml::from_str(&data) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("Failed to parse config {:?}, using defaults: {}", path, e);
+                // keeps the broken file around instead of saving the defaults over it
+                let backup = sibling(path, ".bak");
+                match std::fs::rename(path, &backup) {
+                    Ok(_) => {
+                        log::warn!("Moved the broken config to {:?}", backup);
+                        Self::default()
+                    }
+                    Err(e) => {
+                        log::error!("Failed to move the broken config, settings won't be saved: {}", e);
+                        Self { read_only: true, ..Self::default() }
+                    }
+                }
+            }
+        }
+    }
+
+    /// writes to a temporary file first so a partial write can't break the config
+    pub fn save(&self, path: &Path) {
+        if self.read_only {
+            return;
+        }
+        let data = match toml::to_string_pretty(self) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("Failed to serialize config: {}", e);
+                return;
+            }
+        };
+        let tmp = sibling(path, ".tmp");
+        if let Err(e) = std::fs::write(&tmp, data).and_then(|_| std::fs::rename(&tmp, path)) {
+            log::error!("Failed to write config {:?}: {}", path, e);
+        }
+    }
 }
@@ -4,4 +4,11 @@ pub const NOTESETS_DIR: &'static str = "data/notesets";
 pub const HITSETS_DIR: &'static str = "data/hitsets";
 pub const CURSORSETS_DIR: &'static str = "data/cursorsets";
 pub const LOG_FILE: &'static str = "data/flux.log.txt";
-pub const MAX_AR_AD: f64 = 500.0;
\ No newline at end of file
+pub const CONFIG_FILE: &'static str = "data/config.toml";
+pub const MAX_AR_AD: f64 = 500.0;
+pub const MAP_PACK_EXT: &'static str = "fluxpack";
+pub const REPLAY_DIR: &'static str = "data/replays";
+pub const REPLAY_EXT: &'static str = "fluxreplay";
+pub const HISTORY_FILE: &'static str = "data/history.toml";
+pub const SKINS_DIR: &'static str = "data/skins";
+pub const SKIN_CACHE_DIR: &'static str = "data/skins/.cache";
@@ -1,10 +1,28 @@
-use nannou::{prelude::*, winit::dpi::{PhysicalPosition}};
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+use flux_engine::HIT_PLANE;
+use nannou::prelude::*;
+
+use crate::{managers::notes_manager::draw_tinted_rotated, sets::{cursorset::FluxCursorset, manifest::{FluxTrailManifest, FluxTrailSpawn, FluxTrailStyle}}};
+
+use super::{camera::FluxCamera, config::FluxCursorRegion};
+
+struct FluxTrailPoint {
+    pos: Vec2,
+    at: Instant,
+}
 
 pub struct FluxCursor {
     pub x: f32,
     pub y: f32,
-    pub textures: Vec<wgpu::Texture>,
     size: f32,
+    /// recent positions, oldest first
+    trail: VecDeque<FluxTrailPoint>,
+    /// the rate spawn counts intervals from here, the distance spawn keeps the last frame here
+    last_spawn: Option<Instant>,
+    /// spins are timed from here
+    created: Instant,
+    hit_at: Option<Instant>,
 }
 
 impl FluxCursor {
@@ -13,37 +31,165 @@ impl FluxCursor {
             x: 0.0,
             y: 0.0,
             size: cursor_size,
-            textures: vec![],
+            trail: VecDeque::new(),
+            last_spawn: None,
+            created: Instant::now(),
+            hit_at: None,
+        }
+    }
+    /// draws the cursor and its trail where they are on the hit plane seen through `camera`
+    pub fn draw(&self, draw: Draw, cursorset: &FluxCursorset, camera: &FluxCamera) {
+        let texture = match cursorset.texture() {
+            Some(texture) => texture,
+            None => return,
+        };
+        let manifest = &cursorset.manifest;
+        let sprite = &manifest.sprite;
+        let trail = &manifest.trail;
+        let angle = (manifest.rotation * self.created.elapsed().as_secs_f32()).to_radians();
+        if trail.enabled {
+            // 1 for a new point down to 0 at the end of its lifetime
+            let life = |p: &FluxTrailPoint| (1.0 - p.at.elapsed().as_secs_f32() * 1000.0 / trail.lifetime.max(1.0)).clamp(0.0, 1.0);
+            let size = |life: f32| self.size * (trail.end_scale + (1.0 - trail.end_scale) * life);
+            match trail.style {
+                FluxTrailStyle::Ghosts => {
+                    for p in self.trail.iter() {
+                        let (pos, scale) = match camera.project(p.pos, HIT_PLANE) {
+                            Some(projected) => projected,
+                            None => continue,
+                        };
+                        let life = life(p);
+                        let mut color = sprite.tint.rgba();
+                        color.alpha *= trail.opacity * life;
+                        let size = size(life) * scale;
+                        draw_tinted_rotated(&draw, texture, sprite.rect(pos.x, pos.y, size, size), angle, color);
+                    }
+                }
+                FluxTrailStyle::Ribbon => {
+                    let head = FluxTrailPoint { pos: Vec2::new(self.x, self.y), at: Instant::now() };
+                    let points: Vec<&FluxTrailPoint> = self.trail.iter().chain(std::iter::once(&head)).collect();
+                    for pair in points.windows(2) {
+                        let (a, b) = (pair[0], pair[1]);
+                        let (pa, pb) = match (camera.project(a.pos, HIT_PLANE), camera.project(b.pos, HIT_PLANE)) {
+                            (Some(pa), Some(pb)) => (pa, pb),
+                            _ => continue,
+                        };
+                        let normal = (pb.0 - pa.0).normalize_or_zero().perp();
+                        let (life_a, life_b) = (life(a), life(b));
+                        let wa = size(life_a) * sprite.scale * pa.1 / 4.0;
+                        let wb = size(life_b) * sprite.scale * pb.1 / 4.0;
+                        let mut color = sprite.tint.rgba();
+                        color.alpha *= trail.opacity * life_b;
+                        draw.quad()
+                            .points(pa.0 + normal * wa, pb.0 + normal * wb, pb.0 - normal * wb, pa.0 - normal * wa)
+                            .color(color);
+                    }
+                }
+            }
+        }
+        if let Some((pos, scale)) = camera.project(Vec2::new(self.x, self.y), HIT_PLANE) {
+            let size = self.size * scale * self.hit_scale(manifest.hit_scale, manifest.hit_scale_time);
+            draw_tinted_rotated(&draw, texture, sprite.rect(pos.x, pos.y, size, size), angle, sprite.tint.rgba());
+        }
+    }
+    /// starts the scale on hit effect
+    pub fn hit(&mut self) {
+        self.hit_at = Some(Instant::now());
+    }
+    /// `scale` right after a hit, eased back to 1 over `time` ms
+    fn hit_scale(&self, scale: f32, time: f32) -> f32 {
+        let t = match self.hit_at {
+            Some(at) => (at.elapsed().as_secs_f32() * 1000.0 / time.max(1.0)).min(1.0),
+            None => return 1.0,
+        };
+        let eased = t * (2.0 - t);
+        scale + (1.0 - scale) * eased
+    }
+    /// adds trail points up to the current position, once per frame.
+    /// points are spaced by time or distance so the trail looks the same at any frame rate
+    pub fn update_trail(&mut self, trail: &FluxTrailManifest) {
+        if !trail.enabled {
+            self.trail.clear();
+            self.last_spawn = None;
+            return;
+        }
+        let now = Instant::now();
+        let pos = Vec2::new(self.x, self.y);
+        while self.trail.front().map_or(false, |p| now.duration_since(p.at).as_secs_f32() * 1000.0 > trail.lifetime) {
+            self.trail.pop_front();
+        }
+        let last = self.trail.back().map(|p| p.pos);
+        match trail.spawn {
+            FluxTrailSpawn::Rate => {
+                let interval = 1.0 / trail.rate.max(1.0);
+                let since = self.last_spawn.get_or_insert(now);
+                let start = *since;
+                let due = (now.duration_since(start).as_secs_f32() / interval).floor() as usize;
+                if due == 0 {
+                    return;
+                }
+                // keeps the leftover part of an interval so the rate doesn't depend on the frame rate
+                *since = start + Duration::from_secs_f32(interval * due as f32);
+                let from = last.unwrap_or(pos);
+                if from == pos {
+                    return;
+                }
+                // fill in the points between frames along the way the cursor moved,
+                // each at the time it was due so they fade out evenly
+                for k in due.saturating_sub(trail.length.max(1)) + 1..=due {
+                    let t = k as f32 / due as f32;
+                    let at = start + Duration::from_secs_f32(interval * k as f32);
+                    self.trail.push_back(FluxTrailPoint { pos: from.lerp(pos, t), at });
+                }
+            }
+            FluxTrailSpawn::Distance => {
+                let spacing = trail.spacing.max(1.0);
+                // when the last frame was, points between frames get times in between
+                let frame_start = self.last_spawn.replace(now).unwrap_or(now);
+                let frame = now.duration_since(frame_start);
+                let mut from = match last {
+                    Some(last) => last,
+                    None => {
+                        self.trail.push_back(FluxTrailPoint { pos, at: now });
+                        pos
+                    }
+                };
+                let distance = from.distance(pos);
+                let mut travelled = 0.0;
+                while from.distance(pos) >= spacing {
+                    from += (pos - from).normalize() * spacing;
+                    travelled += spacing;
+                    let at = frame_start + frame.mul_f32(travelled / distance);
+                    self.trail.push_back(FluxTrailPoint { pos: from, at });
+                }
+            }
+        }
+        while self.trail.len() > trail.length {
+            self.trail.pop_front();
         }
     }
-    pub fn draw(&self, draw: Draw, index: usize) {
-        draw.texture(&self.textures[index]).x_y(self.x, self.y).w_h(self.size, self.size);
+    /// moves by raw mouse motion, `dy` is downwards like the motion events
+    pub fn move_by(&mut self, dx: f32, dy: f32, sens: f32) {
+        self.x += dx * sens;
+        self.y -= dy * sens;
     }
-    pub fn cursor_move(&mut self, mp: Point2, sens: f32) {
-        self.x = mp.x * sens;
-        self.y = mp.y * sens;
+    /// puts the cursor where `mouse` is in `region` of the window, mapped onto the play area
+    pub fn move_absolute(&mut self, mouse: Point2, window: Rect, region: &FluxCursorRegion, pw: f32, ph: f32) {
+        let u = (mouse.x - window.left()) / window.w();
+        let v = (window.top() - mouse.y) / window.h();
+        let u = (u - region.x) / region.w.max(0.01);
+        let v = (v - region.y) / region.h.max(0.01);
+        self.x = (u - 0.5) * pw;
+        self.y = (0.5 - v) * ph;
     }
     pub fn change_cursor_size(&mut self, size: f32) {
         self.size = size;
     }
 
-    pub fn lock_cursor_to_play_area(&mut self, pw: f32, ph: f32) {
-        self.x = self.x.clamp(-(pw/2.0), pw/2.0);
-        self.y = self.y.clamp(-(ph/2.0),ph/2.0);
-    }
-
-    pub fn lock_real_cursor_to_play_area(&mut self, app: &App, window: WindowId, sens: f32, edge_buffer: f32, pw: f32, ph: f32) {
-        let w = app.window(window).unwrap();
-        let x = app.mouse.x + (app.window_rect().w() as f32 / 2.0);
-        let y = (app.window_rect().h() as f32 / 2.0) - app.mouse.y;
-        if app.mouse.x > ((pw / 2.0) / sens) + edge_buffer * 1.5 {
-            w.winit_window().set_cursor_position(PhysicalPosition::new(x - 10.0, y)).unwrap();
-        }else if app.mouse.x < -((pw / 2.0) / sens) - edge_buffer {
-            w.winit_window().set_cursor_position(PhysicalPosition::new(x + 10.0, y)).unwrap();
-        } else if app.mouse.y > ((ph / 2.0) / sens) + edge_buffer * 1.5 {
-            w.winit_window().set_cursor_position(PhysicalPosition::new(x, y + 10.0)).unwrap();
-        } else if app.mouse.y < -((ph / 2.0) / sens) - edge_buffer {
-            w.winit_window().set_cursor_position(PhysicalPosition::new(x, y - 10.0)).unwrap();
-        }
+    /// keeps the cursor within `edge_buffer` of the play area
+    pub fn lock_cursor_to_play_area(&mut self, pw: f32, ph: f32, edge_buffer: f32) {
+        let (hw, hh) = (pw / 2.0 + edge_buffer, ph / 2.0 + edge_buffer);
+        self.x = self.x.clamp(-hw, hw);
+        self.y = self.y.clamp(-hh, hh);
     }
 }
\ No newline at end of file
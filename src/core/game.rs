@@ -1,56 +1,240 @@
-use std::path::PathBuf;
+use std::path::Path;
 
+use flux_engine::{autoplay::FluxAutoplay, mods::FluxMod, stats::FluxStats};
+use flux_map::{FluxMap, replay::{FluxReplay, FluxReplayFrame}};
 use nannou::prelude::*;
 
-use crate::{managers::{time_manager::FluxTimeManager, notes_manager::FluxNotesManager, audio_manager::FluxAudioManager, stats_manager::FluxStatsManager}, sets::{hitset::FluxHitset, cursorset::FluxCursorset, noteset::FluxNoteset}, ui::hud::FluxHud, Model};
+use crate::{managers::{time_manager::FluxTimeManager, effects_manager::FluxEffectsManager, notes_manager::{FluxNotesManager, draw_tinted}, audio_manager::FluxAudioManager, replay_manager::FluxReplayManager, practice_manager::FluxPracticeManager}, sets::{hitset::FluxHitset, cursorset::FluxCursorset, noteset::FluxNoteset, skin::{FluxSkin, FluxSkinError}}, ui::hud::FluxHud, Model};
 
-use super::{cursor::FluxCursor, maploader::FluxMap, config::FluxConfig};
-use super::constants::*;
+use super::{camera::FluxCamera, cursor::FluxCursor, maploader::FluxMaploader, config::FluxConfig, constants::HISTORY_FILE, history::{FluxHistory, FluxScore}};
 
 pub struct FluxGame {
     pub map: FluxMap,
+    pub difficulty: String,
     pub config: FluxConfig,
     pub audio_manager: FluxAudioManager,
     pub notes_manager: FluxNotesManager,
     pub time_manager: FluxTimeManager,
-    pub stats_manager: FluxStatsManager,
+    pub stats: FluxStats,
+    pub replay_manager: FluxReplayManager,
     pub cursorset: FluxCursorset,
     pub hitset: FluxHitset,
     pub noteset: FluxNoteset,
+    pub skin: FluxSkin,
+    pub effects: FluxEffectsManager,
     pub cursor: FluxCursor,
+    /// moves the cursor onto every note by itself while set
+    pub autoplay: Option<FluxAutoplay>,
+    pub practice: FluxPracticeManager,
+    pub history: FluxHistory,
+    /// the best completed play of the map before the last play finished
+    pub previous_best: Option<FluxScore>,
 }
 
 impl FluxGame {
     pub fn new(app: &App, config: FluxConfig) -> Self {
         Self {
-            map: FluxMap::empty(),
+            map: FluxMap::new(),
+            difficulty: String::new(),
             audio_manager: FluxAudioManager::new(),
             notes_manager: FluxNotesManager::new(),
             time_manager: FluxTimeManager::new(config.clone().audio.speed, config.clone().audio.offset),
-            stats_manager: FluxStatsManager::new(),
+            stats: FluxStats::new(),
+            replay_manager: FluxReplayManager::new(),
             hitset: FluxHitset::new(),
             cursorset: FluxCursorset::new(),
             noteset: FluxNoteset::new(),
+            skin: FluxSkin::default(),
+            effects: FluxEffectsManager::new(),
             cursor: FluxCursor::new(config.clone().cursor.size),
+            autoplay: None,
+            practice: FluxPracticeManager::new(),
+            history: FluxHistory::load(Path::new(HISTORY_FILE)),
+            previous_best: None,
             config,
         }
     }
 
+    /// clears everything about the current play, the stats included
     pub fn reset(&mut self) {
+        self.stats.reset();
         self.time_manager.reset();
         self.audio_manager.reset();
         self.notes_manager.reset();
+        self.replay_manager.reset();
+        self.practice.reset();
+        self.effects.reset();
+        self.autoplay = None;
     }
 
-    pub fn update_notes(&mut self, app: &App) {
-        self.notes_manager.move_notes(
-            app, 
-            &mut self.stats_manager, 
-            &self.time_manager, 
-            &self.config, 
-            &mut self.audio_manager, 
-            &mut self.hitset, 
-            &mut self.cursorset)
+    /// turns autoplay on or off for the inserted map
+    pub fn set_autoplay(&mut self, enabled: bool) {
+        self.autoplay = match self.map.difficulties.get(&self.difficulty) {
+            Some(notes) if enabled => Some(FluxAutoplay::new(notes, self.config.misc.autoplay_easing, &self.config.mods)),
+            _ => None,
+        };
+    }
+
+    /// true once health ran out, the song is paused until the play is retried or quit
+    pub fn failed(&self) -> bool {
+        self.notes_manager.engine.failed()
+    }
+
+    /// true once every note is judged and the song has played out
+    pub fn results_ready(&self) -> bool {
+        !self.practice.enabled && self.notes_manager.finished() && !self.failed() && self.audio_manager.song_finished()
+    }
+
+    /// starts practicing the inserted map from `start_ms`, practice plays can't fail and aren't recorded
+    pub fn start_practice(&mut self, start_ms: f64) {
+        self.practice.start(start_ms);
+        self.notes_manager.engine.config.mods.set(FluxMod::NoFail, true);
+        self.start_audio();
+        self.seek(self.practice.start_ms);
+    }
+
+    /// jumps the song, the clock and the notes to `ms`, the stats start over
+    pub fn seek(&mut self, ms: f64) {
+        let ms = ms.max(0.0);
+        self.audio_manager.seek_song(ms + self.time_manager.song_timer.offset as f64);
+        self.audio_manager.set_song_speed(self.config.audio.speed);
+        self.time_manager.seek(ms);
+        self.notes_manager.seek(ms);
+        self.effects.reset();
+        self.stats.reset();
+    }
+
+    /// starts the current map over, a watched replay is watched again
+    pub fn retry(&mut self) {
+        if self.practice.enabled {
+            self.seek(self.practice.restart_point());
+            return;
+        }
+        let map = std::mem::replace(&mut self.map, FluxMap::new());
+        let difficulty = self.difficulty.clone();
+        let autoplay = self.autoplay.is_some();
+        let replay = self.replay_manager.watched_replay();
+        self.reset();
+        match replay {
+            Some(replay) => self.play_replay(map, replay),
+            None => {
+                self.insert_map(map, difficulty);
+                self.set_autoplay(autoplay);
+                self.start_audio();
+            }
+        }
+    }
+
+    /// watches the replay of the play that just finished
+    pub fn watch_last_replay(&mut self) {
+        let replay = match self.replay_manager.last.clone() {
+            Some(replay) => replay,
+            None => return,
+        };
+        let map = std::mem::replace(&mut self.map, FluxMap::new());
+        self.reset();
+        self.play_replay(map, replay);
+    }
+
+    /// whether the cursor is moved by a replay or autoplay instead of the mouse
+    pub fn drives_cursor(&self) -> bool {
+        self.replay_manager.playing_back || self.autoplay.is_some()
+    }
+
+    pub fn update_notes(&mut self) {
+        if self.practice.loop_due(self.time_manager.song_timer.current_ms) {
+            self.seek(self.practice.restart_point());
+        }
+        if self.time_manager.paused || self.notes_manager.finished() {
+            return;
+        }
+        let now = self.time_manager.song_timer.current_ms;
+        let (w, h) = (self.config.misc.play_area_width.max(1.0), self.config.misc.play_area_height.max(1.0));
+        // replays judge the notes at the recorded frame times so they play out the same way
+        let frames = if self.replay_manager.playing_back {
+            self.replay_manager.frames_until(now)
+        } else {
+            let (x, y) = match self.autoplay.as_ref() {
+                Some(autoplay) => autoplay.cursor_at(now),
+                None => (self.cursor.x / w, self.cursor.y / h),
+            };
+            let frame = FluxReplayFrame { ms: now, x, y };
+            self.replay_manager.record(frame);
+            vec![frame]
+        };
+        let (hits, combo) = (self.stats.notes_hit, self.stats.combo);
+        for frame in frames {
+            if self.drives_cursor() {
+                self.cursor.x = frame.x * w;
+                self.cursor.y = frame.y * h;
+            }
+            let events = self.notes_manager.move_notes(
+                frame.ms,
+                Vec2::new(frame.x, frame.y), 
+                &mut self.stats, 
+                &mut self.audio_manager, 
+                &mut self.hitset, 
+                &mut self.cursorset);
+            self.effects.update(&events, &self.notes_manager, &self.noteset, &self.skin);
+        }
+        if self.stats.notes_hit > hits {
+            self.cursor.hit();
+        }
+        self.effects.combo(combo, self.stats.combo, self.skin.manifest.effects.pulse_combo);
+        if self.notes_manager.finished() {
+            if self.failed() {
+                self.audio_manager.pause_song();
+                log::info!("Failed at {:.0}ms with {:.2}% accuracy", now, self.stats.accuracy());
+            }
+            self.replay_manager.finish();
+            self.previous_best = self.replay_manager.last.as_ref()
+                .and_then(|replay| self.history.best(&replay.map_hash_hex(), &replay.difficulty).cloned());
+            // watched replays and autoplay aren't the player's own results
+            if !self.replay_manager.watched() && self.autoplay.is_none() && !self.practice.enabled {
+                self.save_result();
+            }
+        }
+    }
+
+    fn save_result(&mut self) {
+        let replay = match self.replay_manager.last.as_ref() {
+            Some(replay) => replay,
+            None => return,
+        };
+        let score = FluxScore {
+            accuracy: self.stats.accuracy(),
+            score: self.stats.score,
+            max_combo: self.stats.max_combo,
+            misses: self.stats.notes_missed,
+            mods: replay.mods.clone(),
+            speed: replay.settings.speed,
+            date: replay.timestamp,
+            failed: self.stats.failed,
+            replay: self.replay_manager.last_path.clone(),
+        };
+        self.history.add(&replay.map_hash_hex(), &replay.difficulty, score, Path::new(HISTORY_FILE));
+    }
+
+    /// loads the picked sets, the sets a skin has take their place.
+    /// a skin that fails to load is dropped and the picked sets are used
+    pub fn load_sets(&mut self, app: &App, skin: Option<&Path>, noteset: &Path, hitset: &Path, cursorset: &Path) -> Result<(), FluxSkinError> {
+        let (skin, result) = match skin.map(|path| FluxSkin::load(app, path)) {
+            Some(Ok(skin)) => (skin, Ok(())),
+            Some(Err(e)) => (FluxSkin::default(), Err(e)),
+            None => (FluxSkin::default(), Ok(())),
+        };
+        self.skin = skin;
+        self.noteset.load_from_path(app, self.skin.noteset_dir().unwrap_or_else(|| noteset.to_path_buf()));
+        self.hitset.load_from_path(self.skin.hitset_dir().unwrap_or_else(|| hitset.to_path_buf()));
+        if let Some(miss) = &self.skin.miss_sound {
+            self.hitset.miss_sounds = vec![miss.clone()];
+        }
+        self.cursorset.load_from_path(app, self.skin.cursorset_dir().unwrap_or_else(|| cursorset.to_path_buf()));
+        result
+    }
+
+    pub fn update_cursor_trail(&mut self) {
+        self.cursor.update_trail(&self.cursorset.manifest.trail);
     }
 
     pub fn new_config(&mut self, config: FluxConfig) {
@@ -59,55 +243,112 @@ impl FluxGame {
     
     pub fn pause_game(&mut self) {
         self.audio_manager.pause_song();
-        self.time_manager.pause_timer.start();
+        self.time_manager.pause();
     }
 
     pub fn play_game(&mut self) {
-        self.time_manager.update_paused_total();
         self.audio_manager.resume_song();
-        self.time_manager.unpause_timer.start();
+        self.time_manager.resume();
+    }
+
+    /// resumes after a countdown so the player can get back into position
+    pub fn request_resume(&mut self) {
+        if self.time_manager.paused && !self.time_manager.resuming {
+            self.time_manager.start_resume();
+        }
     }
 
-    pub fn insert_map(&mut self, map: FluxMap) {
+    /// how far the play is from 0 to 1, by song time against the last note
+    pub fn progress(&self) -> f64 {
+        let length = self.map.difficulties.get(&self.difficulty)
+            .map_or(0, |notes| FluxMap::length_ms(notes)) as f64;
+        (self.time_manager.song_timer.current_ms / length.max(1.0)).clamp(0.0, 1.0)
+    }
+
+    pub fn update_time(&mut self) {
+        if self.time_manager.resume_due() {
+            self.play_game();
+        }
+        let position = self.audio_manager.song_position_ms();
+        self.time_manager.update(position);
+    }
+
+    pub fn insert_map(&mut self, map: FluxMap, difficulty: String) {
+        self.difficulty = if map.difficulties.contains_key(&difficulty) {
+            difficulty
+        } else {
+            FluxMaploader::default_difficulty(&map)
+        };
         self.map = map;
         self.notes_manager.load_maps(
             &self.config, 
             &self.map,
+            &self.difficulty,
             &self.noteset, 
             &self.hitset, 
             &self.cursorset)
     }
 
+    /// plays `replay` on `map` with the settings it was recorded with
+    pub fn play_replay(&mut self, map: FluxMap, replay: FluxReplay) {
+        FluxReplayManager::apply_settings(&mut self.config, &replay);
+        let difficulty = replay.difficulty.clone();
+        self.stats.reset();
+        self.insert_map(map, difficulty);
+        self.replay_manager.start_playback(replay);
+        self.start_audio();
+    }
+
     pub fn start_audio(&mut self) {
+        if !self.replay_manager.playing_back && !self.practice.enabled {
+            self.replay_manager.start_recording(&self.map, &self.difficulty, &self.config, self.autoplay.is_some());
+        }
+        self.time_manager.set_speed(self.config.audio.speed);
         self.audio_manager.play_song(&self.map, &self.config);
-        self.time_manager.song_timer.start();
-        self.time_manager.unpause_timer.start();
+        self.time_manager.start();
     }
 
     pub fn set_speed(&mut self, speed: f64) {
         self.config.audio.speed = speed;
+        self.audio_manager.set_song_speed(speed);
+        self.time_manager.set_speed(speed);
+        self.notes_manager.engine.set_speed(speed);
     }
 
     pub fn draw_before_loaded_map(&self, _draw: Draw) {
     }
     
     pub fn draw_play_game(&self, app: &App, draw: Draw) {
+        let camera = FluxCamera::new(&self.config, Vec2::new(self.cursor.x, self.cursor.y), app.window_rect().h());
+        if let Some(background) = &self.skin.background {
+            draw_tinted(&draw, background, app.window_rect(), lin_srgba(1.0, 1.0, 1.0, self.skin.manifest.background_opacity));
+        }
+        FluxHud::draw_border(&draw, &self.skin, &camera);
         FluxHud::draw(
             app, 
             draw.clone(), 
             &self.map, 
             &self.config, 
-            &self.stats_manager, 
-            &self.time_manager);
+            &self.stats, 
+            &self.time_manager,
+            &self.notes_manager.engine,
+            self.autoplay.is_some(),
+            &self.skin);
+
+        self.effects.render_background(&draw, &self.config, &self.skin, &camera);
 
-        self.notes_manager.clone().render(
+        self.notes_manager.render(
             app, 
             draw.clone(), 
             &self.noteset, 
             &self.config, 
-            &self.time_manager, 
-            &self.notes_manager);
+            &self.time_manager,
+            &camera);
+
+        self.effects.render(&draw, &self.notes_manager, &self.noteset, &self.config, &self.skin, &camera);
         
-        self.cursor.draw(draw.clone(), self.cursorset.index);
+        self.practice.render(app, draw.clone(), &self.config, self.time_manager.song_timer.current_ms);
+
+        self.cursor.draw(draw.clone(), &self.cursorset, &camera);
     }
 }
\ No newline at end of file
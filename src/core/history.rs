@@ -0,0 +1,139 @@
+use std::{path::{Path, PathBuf}, collections::BTreeMap, cmp::Ordering, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Serialize, Deserialize};
+
+/// the result of one play that was finished or failed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FluxScore {
+    pub accuracy: f64,
+    pub score: u64,
+    pub max_combo: u32,
+    pub misses: usize,
+    pub mods: Vec<String>,
+    pub speed: f64,
+    /// unix seconds
+    pub date: u64,
+    pub failed: bool,
+    pub replay: Option<PathBuf>,
+}
+
+impl FluxScore {
+    /// leaderboard order, completed plays first, then by score and accuracy
+    pub fn rank(&self, other: &Self) -> Ordering {
+        self.failed.cmp(&other.failed)
+            .then(other.score.cmp(&self.score))
+            .then(other.accuracy.total_cmp(&self.accuracy))
+    }
+
+    /// how long ago the play was, like "3d ago"
+    pub fn age(&self) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let secs = now.saturating_sub(self.date);
+        match secs {
+            0..=59 => String::from("just now"),
+            60..=3599 => format!("{}m ago", secs / 60),
+            3600..=86399 => format!("{}h ago", secs / 3600),
+            _ => format!("{}d ago", secs / 86400),
+        }
+    }
+}
+
+/// every play saved on this machine, by map content hash and then difficulty
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FluxHistory {
+    #[serde(default)]
+    pub maps: BTreeMap<String, BTreeMap<String, Vec<FluxScore>>>,
+    /// set when a broken history file couldn't be moved aside, so it isn't written over
+    #[serde(skip)]
+    read_only: bool,
+}
+
+/// `path` with `ext` added after its whole file name, like "history.toml.bak"
+pub fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(ext);
+    path.with_file_name(name)
+}
+
+impl FluxHistory {
+    pub fn load(path: &Path) -> Self {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(_) => return Self::default(),
+        };
+        match toml::from_str(&data) {
+            Ok(history) => history,
+            Err(e) => {
+                log::error!("Failed to parse score history {:?}: {}", path, e);
+                // keeps the broken file around instead of saving an empty history over it
+                let backup = sibling(path, ".bak");
+                match std::fs::rename(path, &backup) {
+                    Ok(_) => {
+                        log::warn!("Moved the broken score history to {:?}", backup);
+                        Self::default()
+                    }
+                    Err(e) => {
+                        log::error!("Failed to move the broken score history, new scores won't be saved: {}", e);
+                        Self { read_only: true, ..Self::default() }
+                    }
+                }
+            }
+        }
+    }
+
+    /// writes to a temporary file first so a partial write can't break the history
+    pub fn save(&self, path: &Path) {
+        if self.read_only {
+            return;
+        }
+        let data = match toml::to_string_pretty(self) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("Failed to serialize score history: {}", e);
+                return;
+            }
+        };
+        let tmp = sibling(path, ".tmp");
+        if let Err(e) = std::fs::write(&tmp, data).and_then(|_| std::fs::rename(&tmp, path)) {
+            log::error!("Failed to write score history {:?}: {}", path, e);
+        }
+    }
+
+    /// adds `score` to a difficulty of the map with the hex content hash `map_hash`
+    /// and writes the history to `path`
+    pub fn add(&mut self, map_hash: &str, difficulty: &str, score: FluxScore, path: &Path) {
+        self.maps.entry(map_hash.to_string())
+            .or_default()
+            .entry(difficulty.to_string())
+            .or_default()
+            .push(score);
+        self.save(path);
+    }
+
+    /// every play of a difficulty, oldest first
+    pub fn scores(&self, map_hash: &str, difficulty: &str) -> &[FluxScore] {
+        self.maps.get(map_hash)
+            .and_then(|d| d.get(difficulty))
+            .map(|s| s.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// plays of a difficulty from best to worst, failed plays last
+    pub fn leaderboard(&self, map_hash: &str, difficulty: &str) -> Vec<&FluxScore> {
+        let mut scores: Vec<&FluxScore> = self.scores(map_hash, difficulty).iter().collect();
+        scores.sort_by(|a, b| a.rank(b));
+        scores
+    }
+
+    /// the best completed play of a difficulty
+    pub fn best(&self, map_hash: &str, difficulty: &str) -> Option<&FluxScore> {
+        self.leaderboard(map_hash, difficulty).into_iter().find(|s| !s.failed)
+    }
+
+    /// the most accurate completed play of a difficulty
+    pub fn best_accuracy(&self, map_hash: &str, difficulty: &str) -> Option<&FluxScore> {
+        self.scores(map_hash, difficulty).iter()
+            .filter(|s| !s.failed)
+            .max_by(|a, b| a.accuracy.total_cmp(&b.accuracy))
+    }
+}
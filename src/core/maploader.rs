@@ -1,61 +1,70 @@
-pub struct FluxMap {
-    pub artist: String,
-    pub song_name: String,
-    pub mapper: String,
-    pub map_data: String,
-    pub mp3_data: Vec<u8>,
-}
+use std::path::PathBuf;
 
-impl FluxMap {
-    pub fn empty() -> Self {
-        Self {
-            artist: String::from(""),
-            song_name: String::from(""),
-            mapper: String::from(""),
-            map_data: String::from(""),
-            mp3_data: vec![],
-        }
-    }
-}
+use flux_map::{FluxMap, FluxMapError, convert::{convert_data, ConvertError}, pack::{FluxMapPack, FluxPackError, free_map_path}};
+use thiserror::Error;
+
+use super::constants::{MAP_DIR, MAP_PACK_EXT};
 
 pub struct FluxMaploader;
 
 impl FluxMaploader {
-    pub fn load_map(path: String) -> FluxMap {
-        let mut map = FluxMap::empty();
-        
-        let flm_data = std::fs::read(&path).expect("Failed to read map file. SHOULD NOT HAPPEN???");
-
-        // you can probably use an iterator here, but honestly it would probably increase the complexity.
-        // ^^ or you can just use another crate to do this for you.
-        // you can also add additional error checking, but im too lazy.
-
-        let mut index = 0;
-        
-        let artist_size = u16::from_be_bytes((&flm_data[index..index+2]).try_into().unwrap());
-        index += 2;
-        map.artist = String::from_utf8(flm_data[index..index+artist_size as usize].to_vec()).unwrap();
-        index += artist_size as usize;
-
-        let song_name_size = u16::from_be_bytes((&flm_data[index..index+2]).try_into().unwrap());
-        index += 2;
-        map.song_name = String::from_utf8(flm_data[index..index+song_name_size as usize].to_vec()).unwrap();
-        index += song_name_size as usize;
-
-        let mapper_size = u16::from_be_bytes((&flm_data[index..index+2]).try_into().unwrap());
-        index += 2;
-        map.mapper = String::from_utf8(flm_data[index..index+mapper_size as usize].to_vec()).unwrap();
-        index += mapper_size as usize;
-
-        let map_data_size = u32::from_be_bytes((&flm_data[index..index+4]).try_into().unwrap());
-        index += 4;
-        map.map_data = String::from_utf8(flm_data[index..index+map_data_size as usize].to_vec()).unwrap();
-        index += map_data_size as usize;
-
-        map.mp3_data = flm_data[index..].to_vec();
-
-        println!("Map metadata: {},{},{}", map.artist, map.song_name, map.mapper);
-        map
-        
+    pub fn load_map(path: String) -> Result<FluxMap, FluxMapError> {
+        let map = FluxMap::open(PathBuf::from(path))?;
+        println!("Map metadata: {},{},{}",
+            map.get_meta_string("artist").unwrap_or_default(),
+            map.get_meta_string("song_name").unwrap_or_default(),
+            map.get_meta_string("mapper").unwrap_or_default());
+        Ok(map)
     }
-}
\ No newline at end of file
+
+    /// difficulty played when none is picked, "default" if the map has one
+    pub fn default_difficulty(map: &FluxMap) -> String {
+        if map.difficulties.contains_key("default") {
+            return String::from("default");
+        }
+        let mut names: Vec<&String> = map.difficulties.keys().collect();
+        names.sort();
+        names.first().map(|n| n.to_string()).unwrap_or_default()
+    }
+
+    /// unpacks every map in a map pack into the maps directory
+    pub fn import_pack(path: PathBuf) -> Result<Vec<PathBuf>, FluxPackError> {
+        let pack = FluxMapPack::open(path)?;
+        log::info!("Importing map pack: {} by {} ({} maps)", pack.name, pack.author, pack.maps.len());
+        pack.unpack(PathBuf::from(MAP_DIR))
+    }
+
+    /// converts a map or map pack of any supported format into the maps directory
+    /// and returns the paths of the imported maps
+    pub fn import(path: PathBuf) -> Result<Vec<PathBuf>, FluxImportError> {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if ext == MAP_PACK_EXT {
+            return Ok(Self::import_pack(path)?);
+        }
+        let stem = path.file_stem().ok_or(FluxImportError::BadPath(path.clone()))?;
+        let data = std::fs::read(&path)?;
+        let map = convert_data(&data, &ext)?;
+        // a map with the same name is kept, the new one gets a numbered name
+        let to = free_map_path(&PathBuf::from(MAP_DIR), stem);
+        if ext == "flux" {
+            // already a flux map, keep the file as is
+            std::fs::write(&to, data)?;
+        } else {
+            std::fs::write(&to, map.to_bytes())?;
+        }
+        log::info!("Imported map: {:?}", to);
+        Ok(vec![to])
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FluxImportError {
+    #[error("{0}")]
+    Convert(#[from] ConvertError),
+    #[error("{0}")]
+    Pack(#[from] FluxPackError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("bad map path {0:?}")]
+    BadPath(PathBuf),
+}
@@ -1,6 +1,8 @@
+pub mod camera;
 pub mod constants;
 pub mod cursor;
 pub mod game;
+pub mod history;
 pub mod maploader;
 pub mod timer;
 pub mod config;
\ No newline at end of file
@@ -1,46 +1,94 @@
-use std::time::UNIX_EPOCH;
+use std::time::Instant;
 
+// drift from the audio clock above this is snapped instead of smoothed
+const RESYNC_MS: f64 = 50.0;
+// how much of the drift is corrected each sync
+const SMOOTHING: f64 = 0.1;
+
+/// monotonic stopwatch in milliseconds that runs at `speed`.
+/// pausing, seeking and speed changes keep the time exact since the
+/// elapsed time is banked into `base_ms` before anything changes.
 pub struct FluxTimer {
-    pub start_ms: u64,
-    pub current_ms: u64,
+    pub current_ms: f64,
     pub speed: f64,
     pub offset: i64,
+    base_ms: f64,
+    started: Option<Instant>,
 }
 
 impl FluxTimer {
     pub fn new(speed: f64, offset: i64) -> Self {
         Self {
-            start_ms: 0,
-            current_ms: 0,
+            current_ms: 0.0,
             speed,
             offset,
+            base_ms: 0.0,
+            started: None,
         }
     }
 
+    fn now_ms(&self) -> f64 {
+        self.base_ms + self.started.map_or(0.0, |s| s.elapsed().as_secs_f64() * 1000.0 * self.speed)
+    }
+
+    pub fn running(&self) -> bool {
+        self.started.is_some()
+    }
+
     pub fn set_speed(&mut self, speed: f64) {
+        self.base_ms = self.now_ms();
+        if self.running() {
+            self.started = Some(Instant::now());
+        }
         self.speed = speed;
     }
 
-    pub fn start_with_speed(&mut self) {
-        self.start_ms = ((std::time::SystemTime::now().duration_since(UNIX_EPOCH).expect("Time traveler?").as_millis() as i64 + self.offset) as f64 * self.speed) as u64;
+    pub fn start(&mut self) {
+        self.base_ms = -(self.offset as f64);
+        self.started = Some(Instant::now());
+        self.current_ms = self.base_ms;
     }
 
-    pub fn start(&mut self) {
-        self.start_ms = (std::time::SystemTime::now().duration_since(UNIX_EPOCH).expect("Time traveler?").as_millis() as i64 + self.offset) as u64;
+    pub fn pause(&mut self) {
+        self.base_ms = self.now_ms();
+        self.started = None;
+        self.current_ms = self.base_ms;
+    }
+
+    pub fn resume(&mut self) {
+        if !self.running() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    pub fn seek(&mut self, ms: f64) {
+        self.base_ms = ms;
+        if self.running() {
+            self.started = Some(Instant::now());
+        }
+        self.current_ms = ms;
     }
 
     pub fn reset(&mut self) {
-        self.start_ms = 0;
-        self.current_ms = 0;
+        self.base_ms = 0.0;
+        self.started = None;
+        self.current_ms = 0.0;
     }
 
     pub fn update(&mut self) {
-        let current_time = std::time::SystemTime::now().duration_since(UNIX_EPOCH).expect("Time traveler?").as_millis() as f64 * self.speed;
-        self.current_ms = current_time as u64 - self.start_ms;
+        // never go backwards between updates
+        self.current_ms = self.now_ms().max(self.current_ms);
     }
 
-    pub fn update_with_offset_and_speed(&mut self, offset: i64) {
-        let current_time = (std::time::SystemTime::now().duration_since(UNIX_EPOCH).expect("Time traveler?").as_millis() as f64 * self.speed) as i64 - offset;
-        self.current_ms = current_time as u64 - self.start_ms;
+    /// pulls the timer towards a reference clock (the song position) without making it jump
+    pub fn sync(&mut self, reference_ms: f64) {
+        let reference_ms = reference_ms - self.offset as f64;
+        let drift = reference_ms - self.current_ms;
+        if drift.abs() > RESYNC_MS {
+            self.seek(reference_ms);
+            return;
+        }
+        self.base_ms += drift * SMOOTHING;
+        self.update();
     }
-}
\ No newline at end of file
+}
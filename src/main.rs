@@ -4,18 +4,24 @@ mod managers;
 mod ui;
 
 use crate::core::constants::*;
-use crate::core::config::{FluxConfig, FluxNoteConfig, FluxCursorConfig, FluxAudioConfig, FluxMiscConfig, FluxSetsConfig};
-use std::path::Path;
+use crate::core::config::{FluxConfig, FluxCursorMode};
+use std::{path::PathBuf, rc::Rc, cell::RefCell, time::{Duration, Instant}};
 
 use discord_rich_presence::{DiscordIpcClient, DiscordIpc, activity::{self}};
 use ui::map_menu::FluxMapMenuUI;
 use ui::settings::FluxSettingsUI;
+use ui::toast::FluxToastUI;
+use ui::fail::{FluxFailUI, FluxFailAction};
+use ui::results::{FluxResultsUI, FluxResultsAction};
+use ui::pause::{FluxPauseUI, FluxPauseAction};
 use crate::core::game::{FluxGame};
 use log::LevelFilter;
 use log4rs::{append::file::FileAppender, encode::pattern::PatternEncoder, Config, config::{Appender, Root}};
 use crate::core::maploader::FluxMaploader;
-use nannou::prelude::*;
-use nannou_egui::{Egui, egui::{self, Button, FontDefinitions}};
+use crate::managers::practice_manager::{PRACTICE_SEEK_MS, PRACTICE_SPEED_STEP};
+use flux_map::replay::FluxReplay;
+use nannou::{prelude::*, winit::event::DeviceEvent};
+use nannou_egui::{Egui, egui::{self, FontDefinitions}};
 
 
 fn main() {
@@ -30,56 +36,50 @@ fn main() {
         .build(LevelFilter::Warn)).unwrap();
     log4rs::init_config(config).unwrap();
     log_panics::init();
-    nannou::app(model).update(update).loop_mode(LoopMode::RefreshSync).run();
+    nannou::app(model).event(event).update(update).loop_mode(LoopMode::RefreshSync).run();
 }
 
+const RPC_UPDATE_INTERVAL: Duration = Duration::from_millis(2500);
+// how long the retry key has to be held
+const HOLD_RETRY: Duration = Duration::from_millis(600);
+
 #[derive(PartialEq)]
 enum FluxState {
     InitGame,
     MapMenu,
     PlayMap,
+    Results,
 }
 
-pub const DEFAULT_CONFIG: FluxConfig = FluxConfig {
-    note: FluxNoteConfig {
-        ar: 10.0,
-        ad: 6.0,
-        fs: 10,
-        hitbox: 1.14,
-        approach_time: 0.0,
-    },
-    cursor: FluxCursorConfig {
-        sens: 0.9,
-        size: 70.0,
-        edge_buffer: 20.0,
-    },
-    audio: FluxAudioConfig {
-        volume: 1.0,
-        speed: 1.0,
-        offset: 0,
-    },
-    sets: FluxSetsConfig {
-        note: "rounded",
-        cursor: "default",
-        hit: "thump",
-    },
-    misc: FluxMiscConfig {
-        play_area_width: 0.0,
-        play_area_height: 0.0,
-        debug: false,
-    }
-};
-
 pub struct Model {
     window: window::Id,
     game: FluxGame,
     rpc: DiscordIpcClient,
     state: FluxState,
     captured: bool,
-    gui: Egui,
+    // shared so an egui frame can be open while the UIs borrow the model
+    gui: Rc<RefCell<Egui>>,
     update_rpc: bool,
+    last_rpc_update: Instant,
     settings_ui: FluxSettingsUI,
     map_menu_ui: FluxMapMenuUI,
+    toast_ui: FluxToastUI,
+    results_ui: Option<FluxResultsUI>,
+    /// when the retry key was pressed, while it is held
+    retry_held: Option<Instant>,
+}
+
+/// config file from `--config <path>`, `CONFIG_FILE` otherwise
+fn config_path() -> PathBuf {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            if let Some(path) = args.next() {
+                return PathBuf::from(path);
+            }
+        }
+    }
+    PathBuf::from(CONFIG_FILE)
 }
 
 fn model(app: &App) -> Model {
@@ -92,82 +92,226 @@ fn model(app: &App) -> Model {
         .fullscreen()
         .raw_event(raw_window_event)
         .key_pressed(key_pressed)
+        .key_released(key_released)
         .build()
         .unwrap();
     let w = app.window(window).unwrap();
+    let config_path = config_path();
+    let settings_ui = FluxSettingsUI::new(FluxConfig::load(&config_path), config_path);
     let mut rpc = DiscordIpcClient::new("1093315881104310352").unwrap();
     rpc.connect().unwrap();
 
     Model {
         window,
-        game: FluxGame::new(app, DEFAULT_CONFIG),
+        game: FluxGame::new(app, settings_ui.config.clone()),
         state: FluxState::InitGame,
         captured: false,
-        gui:  Egui::from_window(&w),
+        gui: Rc::new(RefCell::new(Egui::from_window(&w))),
         rpc,
         update_rpc: false,
-        settings_ui: FluxSettingsUI::new(),
+        last_rpc_update: Instant::now(),
+        settings_ui,
         map_menu_ui: FluxMapMenuUI::new(),
+        toast_ui: FluxToastUI::new(),
+        results_ui: None,
+        retry_held: None,
+    }
+}
+
+/// hides the system cursor while the mouse moves the game cursor,
+/// relative mode also grabs it so it can't leave the window
+pub fn set_captured(app: &App, model: &mut Model, captured: bool) {
+    model.captured = captured;
+    let w = app.window(model.window).unwrap();
+    let grab = captured && model.settings_ui.config.cursor.mode == FluxCursorMode::Relative;
+    if let Err(e) = w.set_cursor_grab(grab) {
+        log::warn!("Failed to grab the cursor: {}", e);
     }
+    w.set_cursor_visible(!captured);
 }
 
-fn dropped_file(_app: &App, _model: &mut Model, _path: std::path::PathBuf) {
-    // if model.state != FluxState::MapMenu {
-    //     return;
-    // }
-    // let map = FluxMaploader::load_map(path.into_os_string().into_string().unwrap());
-    // model.state = FluxState::PlayMap;
-    // model.game.insert_map(map);
-    // model.game.play_map_audio();
+/// raw mouse motion, it keeps coming while the cursor is grabbed and isn't tied to the window size
+fn event(_app: &App, model: &mut Model, event: Event) {
+    if let Event::DeviceEvent(_, DeviceEvent::MouseMotion { delta: (dx, dy) }) = event {
+        let cursor = &model.settings_ui.config.cursor;
+        if model.captured && cursor.mode == FluxCursorMode::Relative && !model.game.drives_cursor() {
+            model.game.cursor.move_by(dx as f32, dy as f32, cursor.sens);
+        }
+    }
+}
+
+fn dropped_file(app: &App, model: &mut Model, path: PathBuf) {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    if path.extension().map_or(false, |e| e == REPLAY_EXT) {
+        if model.state != FluxState::MapMenu {
+            return;
+        }
+        match FluxReplay::open(path) {
+            Ok(replay) => watch_replay(app, model, replay),
+            Err(e) => model.toast_ui.error(format!("Failed to open replay {}: {}", name, e)),
+        }
+        return;
+    }
+    match FluxMaploader::import(path) {
+        Ok(imported) => {
+            model.map_menu_ui.refresh();
+            model.toast_ui.info(format!("Imported {} map(s) from {}", imported.len(), name));
+            if model.state == FluxState::MapMenu {
+                if let Some(first) = imported.first() {
+                    model.map_menu_ui.select(first.clone());
+                }
+            }
+        },
+        Err(e) => model.toast_ui.error(format!("Failed to import {}: {}", name, e)),
+    }
+}
+
+/// plays a replay back on the map it was recorded on
+fn watch_replay(app: &App, model: &mut Model, replay: FluxReplay) {
+    let path = match model.map_menu_ui.find_by_hash(&replay.map_hash_hex()) {
+        Some(path) => path,
+        None => {
+            model.toast_ui.error(String::from("The map of this replay is not in the maps directory"));
+            return;
+        }
+    };
+    let map = match FluxMaploader::load_map(path.clone()) {
+        Ok(map) => map,
+        Err(e) => {
+            model.toast_ui.error(format!("Failed to load {}: {}", path, e));
+            return;
+        }
+    };
+    model.game.audio_manager.stop_preview();
+    model.state = FluxState::PlayMap;
+    model.update_rpc = true;
+    model.game.play_replay(map, replay);
+    set_captured(app, model, false);
+}
+
+fn back_to_menu(app: &App, model: &mut Model) {
+    model.game.audio_manager.pause_song();
+    model.state = FluxState::MapMenu;
+    model.results_ui = None;
+    model.retry_held = None;
+    model.game.reset();
+    // replays play with their own settings
+    model.game.new_config(model.settings_ui.config.clone());
+    set_captured(app, model, false);
+    model.update_rpc = true;
+}
+
+/// plays the map again from the fail or results screen
+fn retry(app: &App, model: &mut Model) {
+    model.state = FluxState::PlayMap;
+    model.results_ui = None;
+    model.update_rpc = true;
+    model.game.retry();
+    let captured = !model.game.drives_cursor();
+    set_captured(app, model, captured);
+}
+
+/// pauses the play and frees the cursor for the pause menu
+fn pause(app: &App, model: &mut Model) {
+    model.game.pause_game();
+    set_captured(app, model, false);
+}
+
+/// counts down back into the play
+fn resume(app: &App, model: &mut Model) {
+    model.game.request_resume();
+    let captured = !model.game.drives_cursor();
+    set_captured(app, model, captured);
+}
+
+/// leaves the play for the results screen
+fn show_results(app: &App, model: &mut Model) {
+    model.game.audio_manager.pause_song();
+    model.results_ui = Some(FluxResultsUI::new(&model.game));
+    model.state = FluxState::Results;
+    set_captured(app, model, false);
+}
+
+/// practice keys, true if the key was one of them
+fn practice_key(model: &mut Model, keycode: Key) -> bool {
+    let game = &mut model.game;
+    let now = game.time_manager.song_timer.current_ms;
+    match keycode {
+        Key::R => game.retry(),
+        Key::LBracket => game.practice.set_a(now),
+        Key::RBracket => game.practice.set_b(now),
+        Key::Backslash => game.practice.clear_loop(),
+        Key::Left => game.seek(now - PRACTICE_SEEK_MS),
+        Key::Right => game.seek(now + PRACTICE_SEEK_MS),
+        Key::Up => game.set_speed((game.config.audio.speed + PRACTICE_SPEED_STEP).min(10.0)),
+        Key::Down => game.set_speed((game.config.audio.speed - PRACTICE_SPEED_STEP).max(PRACTICE_SPEED_STEP)),
+        _ => return false,
+    }
+    true
 }
 
 fn key_pressed(app: &App, model: &mut Model, keycode: Key) {
+    if model.state == FluxState::PlayMap && model.game.practice.enabled && practice_key(model, keycode) {
+        return;
+    }
     match keycode {
         Key::Tab => {
             if model.state == FluxState::PlayMap {
-                model.captured = !model.captured;
+                let captured = !model.captured;
+                set_captured(app, model, captured);
             }
-            let w = app.window(model.window).unwrap();
-            w.set_cursor_grab(model.captured).unwrap();
-            w.set_cursor_visible(!model.captured);
         },
-        Key::Back => {
-            model.game.audio_manager.pause_song();
-            model.state = FluxState::MapMenu;
-            model.game.reset();
-            model.captured = false;
-            let w = app.window(model.window).unwrap();
-            w.set_cursor_visible(!model.captured);
-            model.update_rpc = true;
+        Key::Back => back_to_menu(app, model),
+        // skips what is left of the song once every note is judged
+        Key::Return => {
+            if model.state == FluxState::PlayMap && model.game.notes_manager.finished() && !model.game.failed() && !model.game.practice.enabled {
+                show_results(app, model);
+            }
         },
         Key::F1 => {
             model.settings_ui.show_settings = !model.settings_ui.show_settings;
         },
         Key::Space => {
             if model.state != FluxState::PlayMap 
-            || model.game.time_manager.pause_timer.current_ms < 1000 {
+            || model.game.failed()
+            || !model.game.time_manager.can_toggle_pause() {
                 return;
             }
-            model.game.time_manager.paused = !model.game.time_manager.paused;
-            if model.game.time_manager.paused {
-                model.game.pause_game();
-            }
             if !model.game.time_manager.paused {
-                model.game.play_game();
+                pause(app, model);
+            } else if model.game.time_manager.resuming {
+                model.game.time_manager.cancel_resume();
+                pause(app, model);
+            } else {
+                resume(app, model);
+            }
+        },
+        Key::Grave => {
+            if model.state == FluxState::PlayMap && model.retry_held.is_none() {
+                model.retry_held = Some(Instant::now());
             }
         },
         _ => {}, 
     }
 }
 
+fn key_released(_app: &App, model: &mut Model, keycode: Key) {
+    if keycode == Key::Grave {
+        model.retry_held = None;
+    }
+}
+
 fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
-    model.gui.handle_raw_event(event);
+    model.gui.borrow_mut().handle_raw_event(event);
 }
 
 
 fn update(app: &App, model: &mut Model, update: Update) {
     if model.state == FluxState::InitGame {
-        model.settings_ui.clone().init(app, model);
+        let mut settings_ui = model.settings_ui.clone();
+        settings_ui.init(app, model);
+        model.settings_ui = settings_ui;
+        model.map_menu_ui.init();
 
         model.state = FluxState::MapMenu;
         model.update_rpc = true;
@@ -192,33 +336,61 @@ fn update(app: &App, model: &mut Model, update: Update) {
         fonts.family_and_size.insert(egui::TextStyle::Small, (egui::FontFamily::Proportional, 22.0));
 
         
-        model.gui.set_elapsed_time(update.since_start);
-        let ctx = model.gui.begin_frame();
+        let gui_rc = model.gui.clone();
+        let mut gui = gui_rc.borrow_mut();
+        gui.set_elapsed_time(update.since_start);
+        let ctx = gui.begin_frame();
         ctx.set_fonts(fonts);
         
-        model.settings_ui.clone().render(app, model, ctx);
+        let mut map_menu_ui = std::mem::take(&mut model.map_menu_ui);
+        map_menu_ui.render(app, model, &ctx);
+        model.map_menu_ui = map_menu_ui;
+        let mut settings_ui = model.settings_ui.clone();
+        settings_ui.render(app, model, &ctx);
+        model.settings_ui = settings_ui;
+        model.toast_ui.render(&ctx);
+        model.game.audio_manager.update_preview();
+    }
+    if model.state == FluxState::Results {
+        let gui_rc = model.gui.clone();
+        let mut gui = gui_rc.borrow_mut();
+        gui.set_elapsed_time(update.since_start);
+        let ctx = gui.begin_frame();
+        let action = model.results_ui.as_ref().and_then(|results_ui| results_ui.render(&ctx));
+        match action {
+            Some(FluxResultsAction::Retry) => retry(app, model),
+            Some(FluxResultsAction::WatchReplay) => {
+                model.state = FluxState::PlayMap;
+                model.results_ui = None;
+                model.update_rpc = true;
+                model.game.watch_last_replay();
+            },
+            Some(FluxResultsAction::Menu) => back_to_menu(app, model),
+            None => {},
+        }
+        return;
     }
     if model.state != FluxState::PlayMap {
         return;
     }
 
-    if model.game.time_manager.song_timer.current_ms % 2500 == 0 {
+    if model.last_rpc_update.elapsed() >= RPC_UPDATE_INTERVAL {
         model.update_rpc = true;
     }
 
     if model.update_rpc {
-        let artist =String::from_utf8_lossy(model.game.map.meta.get("artist").unwrap());
-        let title = String::from_utf8_lossy(model.game.map.meta.get("song_name").unwrap());
+        let artist = model.game.map.get_meta_string("artist").unwrap_or_default();
+        let title = model.game.map.get_meta_string("song_name").unwrap_or_default();
 
         let details = format!("{} - {}", 
                 artist, 
                 title);
 
         let state = format!("{}:{:02} - {:.02}% - {} Misses - {:.02}x", 
-            ((model.game.time_manager.song_timer.current_ms / 1000) / 60), 
-            (model.game.time_manager.song_timer.current_ms / 1000) % 60, 
-            (model.game.stats_manager.notes_hit as f32 / model.game.stats_manager.note_total as f32) * 100.0, 
-            model.game.stats_manager.note_total,
+            ((model.game.time_manager.song_timer.current_ms as u64 / 1000) / 60), 
+            (model.game.time_manager.song_timer.current_ms as u64 / 1000) % 60, 
+            model.game.stats.accuracy(), 
+            model.game.stats.notes_missed,
             model.settings_ui.config.audio.speed);
 
         let payload = activity::Activity::new()
@@ -230,16 +402,56 @@ fn update(app: &App, model: &mut Model, update: Update) {
                     .large_image("flux"));
         model.rpc.set_activity(payload).unwrap();
         model.update_rpc = false;
+        model.last_rpc_update = Instant::now();
     }
-    if model.captured {
-        // w.set_cursor_position_points(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0).unwrap();
-        model.game.cursor.cursor_move(Point2::new(app.mouse.x, app.mouse.y), model.settings_ui.config.cursor.sens);
+    // relative motion comes in through `event`
+    let config = &model.settings_ui.config;
+    if model.captured && !model.game.drives_cursor() && config.cursor.mode == FluxCursorMode::Absolute {
+        model.game.cursor.move_absolute(app.mouse.position(), app.window_rect(), &config.cursor.region, config.misc.play_area_width, config.misc.play_area_height);
+    }
+    model.game.update_time();
+    model.game.update_notes();
+    model.game.update_cursor_trail();
+    if model.game.failed() {
+        if model.captured {
+            set_captured(app, model, false);
+        }
+        let gui_rc = model.gui.clone();
+        let mut gui = gui_rc.borrow_mut();
+        gui.set_elapsed_time(update.since_start);
+        let ctx = gui.begin_frame();
+        match FluxFailUI::render(&ctx, &model.game.stats) {
+            Some(FluxFailAction::Retry) => retry(app, model),
+            Some(FluxFailAction::Quit) => back_to_menu(app, model),
+            None => {},
+        }
+        return;
+    }
+    if model.retry_held.map_or(false, |held| held.elapsed() >= HOLD_RETRY) {
+        model.retry_held = None;
+        retry(app, model);
+        return;
+    }
+    if model.game.time_manager.paused && !model.game.time_manager.resuming {
+        let gui_rc = model.gui.clone();
+        let mut gui = gui_rc.borrow_mut();
+        gui.set_elapsed_time(update.since_start);
+        let ctx = gui.begin_frame();
+        match FluxPauseUI::render(&ctx, &model.game) {
+            Some(FluxPauseAction::Resume) => resume(app, model),
+            Some(FluxPauseAction::Retry) => retry(app, model),
+            Some(FluxPauseAction::Quit) => back_to_menu(app, model),
+            None => {},
+        }
+        return;
+    }
+    if model.game.results_ready() {
+        show_results(app, model);
+        return;
     }
-    model.game.time_manager.update();
-    model.game.update_notes(app);
     if model.captured {
-        model.game.cursor.lock_real_cursor_to_play_area(app, model.window, model.settings_ui.config.cursor.sens, model.settings_ui.config.cursor.edge_buffer, model.settings_ui.config.misc.play_area_width, model.settings_ui.config.misc.play_area_height);
-        model.game.cursor.lock_cursor_to_play_area(model.settings_ui.config.misc.play_area_width, model.settings_ui.config.misc.play_area_height);
+        let config = &model.settings_ui.config;
+        model.game.cursor.lock_cursor_to_play_area(config.misc.play_area_width, config.misc.play_area_height, config.cursor.edge_buffer);
     }
 }
 
@@ -248,12 +460,25 @@ fn view(app: &App, model: &Model, frame: Frame) {
     draw.background().color(BLACK);
     match model.state {
         FluxState::InitGame => { draw.text("Loading content").font_size(50).width(app.window_rect().w() as f32); },
-        FluxState::MapMenu => model.game.draw_before_loaded_map(draw.clone()),
-        FluxState::PlayMap => model.game.draw_play_game(app, draw.clone()),
+        FluxState::MapMenu => {
+            model.game.draw_before_loaded_map(draw.clone());
+            model.map_menu_ui.draw(app, draw.clone());
+        },
+        FluxState::PlayMap | FluxState::Results => model.game.draw_play_game(app, draw.clone()),
     };
     // draw.text(&format!("{:.2} FPS", app.fps())).right_justify().y((HEIGHT as f32 / 2.0) - 10.0).color(YELLOW).font_size(20).width(WIDTH as f32);
+    if let Some(held) = model.retry_held {
+        // fills up along the top of the window until the retry happens
+        let win = app.window_rect();
+        let progress = (held.elapsed().as_secs_f32() / HOLD_RETRY.as_secs_f32()).min(1.0);
+        draw.rect()
+            .x_y(-win.w() / 2.0 + win.w() * progress / 2.0, win.h() / 2.0 - 5.0)
+            .w_h(win.w() * progress, 10.0)
+            .color(WHITE);
+    }
     draw.to_frame(app, &frame).unwrap();
-    if model.state == FluxState::MapMenu {
-        model.gui.draw_to_frame(&frame).unwrap();
+    let paused = model.game.time_manager.paused && !model.game.time_manager.resuming;
+    if model.state == FluxState::MapMenu || model.state == FluxState::Results || model.game.failed() || (model.state == FluxState::PlayMap && paused) {
+        model.gui.borrow().draw_to_frame(&frame).unwrap();
     }
 }
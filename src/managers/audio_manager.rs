@@ -1,12 +1,33 @@
-use std::io::Cursor;
+use std::{io::Cursor, path::PathBuf, sync::mpsc::{self, Receiver}, time::{Duration, Instant}};
 
-use kira::{manager::{AudioManager, backend::cpal::CpalBackend, AudioManagerSettings}, tween::Tween, sound::static_sound::{StaticSoundData, StaticSoundSettings}, PlaybackRate};
+use kira::{manager::{AudioManager, backend::cpal::CpalBackend, AudioManagerSettings}, tween::Tween, sound::static_sound::{StaticSoundData, StaticSoundSettings, StaticSoundHandle, PlaybackState}, PlaybackRate};
 
-use crate::{core::{maploader::FluxMap, config::FluxConfig}, sets::hitset::FluxHitset};
+use flux_map::FluxMap;
+
+use crate::{core::config::FluxConfig, sets::hitset::FluxHitset};
+
+// how long a preview plays before it fades out and starts again
+const PREVIEW_LENGTH: Duration = Duration::from_secs(15);
+const PREVIEW_FADE: Duration = Duration::from_millis(800);
+// where the preview starts if the map has no "preview_time" metadata
+const PREVIEW_FALLBACK_POINT: f64 = 0.4;
+
+struct FluxPreview {
+    data: StaticSoundData,
+    handle: StaticSoundHandle,
+    started: Instant,
+}
 
 pub struct FluxAudioManager {
     song_manager: AudioManager,
     hitsound_manager: AudioManager,    
+    song: Option<StaticSoundHandle>,
+    /// kept so a song that has played out can be started again by a seek
+    song_data: Option<StaticSoundData>,
+    preview_manager: AudioManager,
+    preview: Option<FluxPreview>,
+    preview_path: Option<String>,
+    preview_rx: Option<Receiver<Option<StaticSoundData>>>,
 }
 
 impl FluxAudioManager {
@@ -14,12 +35,20 @@ impl FluxAudioManager {
         Self {
             song_manager: AudioManager::<CpalBackend>::new(AudioManagerSettings::default()).expect("Failed to create song audio manager"),
             hitsound_manager: AudioManager::<CpalBackend>::new(AudioManagerSettings::default()).expect("Failed to create hitsound audio manager"),
+            song: None,
+            song_data: None,
+            preview_manager: AudioManager::<CpalBackend>::new(AudioManagerSettings::default()).expect("Failed to create preview audio manager"),
+            preview: None,
+            preview_path: None,
+            preview_rx: None,
         }
     }
 
     pub fn reset(&mut self) {
         self.song_manager = AudioManager::<CpalBackend>::new(AudioManagerSettings::default()).expect("Failed to create song audio manager");
         self.hitsound_manager = AudioManager::<CpalBackend>::new(AudioManagerSettings::default()).expect("Failed to create hitsound audio manager");
+        self.song = None;
+        self.song_data = None;
     }
 
     pub fn set_song_volume(&mut self, vol: f64) {
@@ -35,9 +64,47 @@ impl FluxAudioManager {
     }
 
     pub fn play_song(&mut self, map: &FluxMap, config: &FluxConfig) {
-        let cursor = Cursor::new(map.mp3_data.clone());
+        let cursor = Cursor::new(map.music_data.clone());
         let sound_data = StaticSoundData::from_cursor(cursor, StaticSoundSettings::default().playback_rate(PlaybackRate::Factor(config.audio.speed))).expect("Failed to create sound data");
-        self.song_manager.play(sound_data.clone()).unwrap();
+        self.song = Some(self.song_manager.play(sound_data.clone()).unwrap());
+        self.song_data = Some(sound_data);
+    }
+
+    /// moves the song to `ms`, a song that has played out starts again from there
+    pub fn seek_song(&mut self, ms: f64) {
+        let position = (ms / 1000.0).max(0.0);
+        if self.song_finished() {
+            if let Some(data) = self.song_data.clone() {
+                match self.song_manager.play(data.with_modified_settings(|s| s.start_position(position))) {
+                    Ok(song) => self.song = Some(song),
+                    Err(e) => log::warn!("Failed to restart song: {}", e),
+                }
+            }
+            return;
+        }
+        if let Some(song) = self.song.as_mut() {
+            if let Err(e) = song.seek_to(position) {
+                log::warn!("Failed to seek song: {}", e);
+            }
+        }
+    }
+
+    /// where the song is in ms, None if no song is playing
+    pub fn song_position_ms(&self) -> Option<f64> {
+        self.song.as_ref().map(|s| s.position() * 1000.0)
+    }
+
+    /// true once the song has played to its end, or if there is no song
+    pub fn song_finished(&self) -> bool {
+        self.song.as_ref().map_or(true, |s| s.state() == PlaybackState::Stopped)
+    }
+
+    pub fn set_song_speed(&mut self, speed: f64) {
+        if let Some(song) = self.song.as_mut() {
+            song.set_playback_rate(PlaybackRate::Factor(speed), Tween {
+                ..Default::default()
+            }).unwrap();
+        }
     }
 
     pub fn pause_song(&mut self) {
@@ -53,6 +120,87 @@ impl FluxAudioManager {
     }
 
     pub fn play_hitsound(&mut self, hitset: &FluxHitset) {
-        self.song_manager.play(hitset.sounds[hitset.index].clone());
+        if let Some(sound) = hitset.sounds.get(hitset.index) {
+            let _ = self.song_manager.play(sound.clone());
+        }
+    }
+
+    /// plays the set's current miss sound, if it has any
+    pub fn play_miss_sound(&mut self, hitset: &FluxHitset) {
+        if let Some(sound) = hitset.miss_sounds.get(hitset.miss_index) {
+            let _ = self.song_manager.play(sound.clone());
+        }
+    }
+
+    pub fn set_preview_volume(&mut self, vol: f64) {
+        self.preview_manager.main_track().set_volume(vol, Tween {
+            ..Default::default()
+        });
+    }
+
+    /// starts looping a preview of the map at `path`, the audio is decoded on another thread
+    pub fn preview_map(&mut self, path: String) {
+        if self.preview_path.as_ref() == Some(&path) {
+            return;
+        }
+        self.stop_preview();
+        self.preview_path = Some(path.clone());
+
+        let (tx, rx) = mpsc::channel();
+        self.preview_rx = Some(rx);
+        std::thread::spawn(move || {
+            let data = FluxMap::open(PathBuf::from(path)).ok().and_then(|map| {
+                let cursor = Cursor::new(map.music_data.clone());
+                let data = StaticSoundData::from_cursor(cursor, StaticSoundSettings::default()).ok()?;
+                let length = data.duration().as_secs_f64();
+                let start = map.get_meta_string("preview_time")
+                    .and_then(|t| t.trim().parse::<f64>().ok())
+                    .map(|ms| ms / 1000.0)
+                    .filter(|t| *t >= 0.0 && *t < length)
+                    .unwrap_or(length * PREVIEW_FALLBACK_POINT);
+                Some(data.with_settings(StaticSoundSettings::default()
+                    .start_position(start)
+                    .fade_in_tween(Tween { duration: PREVIEW_FADE, ..Default::default() })))
+            });
+            // the receiver is gone if another map was selected in the meantime
+            let _ = tx.send(data);
+        });
+    }
+
+    /// plays decoded previews and restarts them once they have played for `PREVIEW_LENGTH`
+    pub fn update_preview(&mut self) {
+        if let Some(rx) = &self.preview_rx {
+            if let Ok(data) = rx.try_recv() {
+                self.preview_rx = None;
+                if let Some(data) = data {
+                    self.play_preview(data);
+                }
+            }
+        }
+
+        let restart = match &self.preview {
+            Some(preview) => preview.started.elapsed() >= PREVIEW_LENGTH,
+            None => false,
+        };
+        if restart {
+            let mut preview = self.preview.take().unwrap();
+            let _ = preview.handle.stop(Tween { duration: PREVIEW_FADE, ..Default::default() });
+            self.play_preview(preview.data);
+        }
+    }
+
+    fn play_preview(&mut self, data: StaticSoundData) {
+        match self.preview_manager.play(data.clone()) {
+            Ok(handle) => self.preview = Some(FluxPreview { data, handle, started: Instant::now() }),
+            Err(e) => log::warn!("Failed to play preview: {}", e),
+        }
+    }
+
+    pub fn stop_preview(&mut self) {
+        if let Some(mut preview) = self.preview.take() {
+            let _ = preview.handle.stop(Tween { duration: PREVIEW_FADE, ..Default::default() });
+        }
+        self.preview_rx = None;
+        self.preview_path = None;
     }
 }
\ No newline at end of file
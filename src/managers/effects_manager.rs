@@ -0,0 +1,140 @@
+use std::time::Instant;
+
+use flux_engine::{FluxNoteEvent, HIT_PLANE, judgement::FluxJudgement};
+use nannou::prelude::*;
+
+use crate::{core::{config::FluxConfig, camera::FluxCamera}, sets::{manifest::FluxColor, noteset::FluxNoteset, skin::{FluxSkin, FluxMissStyle}}, ui::hud::text};
+
+use super::notes_manager::{FluxNotesManager, draw_tinted};
+
+/// how far along an effect started at `at` that lasts `ms` is, from 0 to 1
+fn progress(at: Instant, ms: f32) -> f32 {
+    (at.elapsed().as_secs_f32() * 1000.0 / ms.max(1.0)).min(1.0)
+}
+
+/// a judged note that is still showing
+struct FluxEffect {
+    index: u32,
+    judgement: FluxJudgement,
+    /// where the note was, in map units
+    x: f32,
+    y: f32,
+    color: FluxColor,
+    at: Instant,
+}
+
+/// Hit and miss effects, judgement popups and the combo pulse.
+/// The skin decides how they look and the config which of them are shown.
+#[derive(Default)]
+pub struct FluxEffectsManager {
+    effects: Vec<FluxEffect>,
+    pulse_at: Option<Instant>,
+}
+
+impl FluxEffectsManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.effects.clear();
+        self.pulse_at = None;
+    }
+
+    /// adds effects for the notes judged in `events` and drops the ones that are done
+    pub fn update(&mut self, events: &[FluxNoteEvent], notes_manager: &FluxNotesManager, noteset: &FluxNoteset, skin: &FluxSkin) {
+        let manifest = &skin.manifest.effects;
+        let longest = manifest.hit_time.max(manifest.miss_time).max(manifest.popup_time);
+        self.effects.retain(|e| progress(e.at, longest) < 1.0);
+        let now = Instant::now();
+        for event in events {
+            if let FluxNoteEvent::Judged { index, judgement, x, y, ms } = *event {
+                self.effects.push(FluxEffect {
+                    index,
+                    judgement,
+                    x,
+                    y,
+                    color: notes_manager.note_color(noteset, index, ms),
+                    at: now,
+                });
+            }
+        }
+    }
+
+    /// pulses the background when the combo goes past a multiple of `every`
+    pub fn combo(&mut self, before: u32, after: u32, every: u32) {
+        if every > 0 && after > before && after / every > before / every {
+            self.pulse_at = Some(Instant::now());
+        }
+    }
+
+    /// the combo pulse, drawn behind the notes
+    pub fn render_background(&self, draw: &Draw, config: &FluxConfig, skin: &FluxSkin, camera: &FluxCamera) {
+        let manifest = &skin.manifest.effects;
+        let t = match self.pulse_at {
+            Some(at) if config.effects.combo_pulse => progress(at, manifest.pulse_time),
+            _ => return,
+        };
+        if let Some(corners) = camera.play_area().filter(|_| t < 1.0) {
+            let mut color = manifest.pulse_color.rgba();
+            color.alpha *= 1.0 - t;
+            draw.polygon()
+                .points(corners)
+                .color(color);
+        }
+    }
+
+    /// hit and miss effects and judgement popups, drawn over the notes
+    pub fn render(&self, draw: &Draw, notes_manager: &FluxNotesManager, noteset: &FluxNoteset, config: &FluxConfig, skin: &FluxSkin, camera: &FluxCamera) {
+        let manifest = &skin.manifest.effects;
+        let sprite = &noteset.manifest.sprite;
+        for e in self.effects.iter() {
+            let rect = match camera.note_rect(e.x, e.y, HIT_PLANE) {
+                Some(rect) => rect,
+                None => continue,
+            };
+            let (x, y, w, h) = (rect.x(), rect.y(), rect.w(), rect.h());
+            let texture = notes_manager.note_texture(noteset, e.index);
+            if e.judgement == FluxJudgement::Miss {
+                let t = progress(e.at, manifest.miss_time);
+                if config.effects.miss && t < 1.0 {
+                    let mut color = manifest.miss_color.rgba();
+                    color.alpha *= 1.0 - t;
+                    match manifest.miss_style {
+                        FluxMissStyle::Cross => {
+                            let s = w.min(h) / 4.0;
+                            draw.line().start(pt2(x - s, y - s)).end(pt2(x + s, y + s)).weight(4.0).color(color);
+                            draw.line().start(pt2(x - s, y + s)).end(pt2(x + s, y - s)).weight(4.0).color(color);
+                        }
+                        FluxMissStyle::Shrink => if let Some(texture) = texture {
+                            let scale = 1.0 - t;
+                            draw_tinted(draw, texture, sprite.rect(x, y, w * scale, h * scale), color);
+                        },
+                    }
+                }
+            } else {
+                let t = progress(e.at, manifest.hit_time);
+                if let Some(texture) = texture.filter(|_| config.effects.hit && t < 1.0) {
+                    let scale = 1.0 + (manifest.hit_scale - 1.0) * t;
+                    let mut color = e.color.rgba();
+                    color.alpha *= manifest.hit_opacity * (1.0 - t);
+                    draw_tinted(draw, texture, sprite.rect(x, y, w * scale, h * scale), color);
+                }
+            }
+
+            let hidden = e.judgement == FluxJudgement::Perfect && config.effects.hide_perfect_popups;
+            let t = progress(e.at, manifest.popup_time);
+            if config.effects.popups && !hidden && t < 1.0 {
+                let mut color = manifest.judgement_colors[e.judgement.index()].rgba();
+                color.alpha *= 1.0 - t;
+                // popups drift up as they fade
+                text(draw, skin, e.judgement.name())
+                    .color(color)
+                    .font_size(manifest.popup_size)
+                    .x(x)
+                    .y(y + h / 2.0 + 20.0 * t)
+                    .width(w * 2.0);
+            }
+        }
+    }
+}
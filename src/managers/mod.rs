@@ -1,4 +1,6 @@
 pub mod audio_manager;
+pub mod effects_manager;
 pub mod notes_manager;
-pub mod stats_manager;
+pub mod practice_manager;
+pub mod replay_manager;
 pub mod time_manager;
\ No newline at end of file
@@ -1,146 +1,185 @@
 use nannou::prelude::*;
 
-use crate::{core::{config::FluxConfig, maploader::FluxMap, constants::MAX_AR_AD}, sets::{noteset::FluxNoteset, hitset::FluxHitset, cursorset::FluxCursorset}};
+use flux_engine::{FluxEngine, FluxNoteEvent, HIT_PLANE, judgement::FluxJudgement, stats::FluxStats};
+use flux_map::FluxMap;
+use crate::{core::{config::FluxConfig, camera::FluxCamera}, sets::{manifest::{FluxColor, parse_palette}, noteset::FluxNoteset, hitset::FluxHitset, cursorset::FluxCursorset}};
 
-use super::{stats_manager::FluxStatsManager, audio_manager::FluxAudioManager, time_manager::FluxTimeManager};
+use super::{audio_manager::FluxAudioManager, time_manager::FluxTimeManager};
 
-#[derive(Clone, PartialEq)]
-pub struct FluxNote {
+/// draws `texture` over `rect` with its colors multiplied by `color`
+pub fn draw_tinted(draw: &Draw, texture: &wgpu::Texture, rect: Rect, color: LinSrgba) {
+    draw_tinted_rotated(draw, texture, rect, 0.0, color);
+}
+
+/// like `draw_tinted`, turned by `radians` around the middle of `rect`
+pub fn draw_tinted_rotated(draw: &Draw, texture: &wgpu::Texture, rect: Rect, radians: f32, color: LinSrgba) {
+    let center = rect.xy();
+    let (sin, cos) = radians.sin_cos();
+    let points = [
+        (rect.top_left(), pt2(0.0, 0.0)),
+        (rect.top_right(), pt2(1.0, 0.0)),
+        (rect.bottom_right(), pt2(1.0, 1.0)),
+        (rect.bottom_left(), pt2(0.0, 1.0)),
+    ].map(|(p, tex_coords)| {
+        let p = p - center;
+        (pt3(center.x + p.x * cos - p.y * sin, center.y + p.x * sin + p.y * cos, 0.0), tex_coords)
+    });
+    draw.polygon()
+        .points_textured(texture, points)
+        .color(color);
+}
+
+/// a missed note held on the hit plane by pushback
+#[derive(Clone)]
+struct FluxPushedNote {
+    index: u32,
     x: f32,
     y: f32,
-    z: f32,
-    index: u32,
-    noteset_index: usize,
-    hitset_index: usize,
-    cursorset_index: usize,
-    spawn_time: u64,
-    ms: u64,
-    hitsound_played: bool,
+    ms: f64,
+    /// song time of the miss
+    missed_at: f64,
 }
 
+/// Drives the engine for the loaded map and draws its notes with the loaded sets.
 #[derive(Clone)]
 pub struct FluxNotesManager {
-    notes: Vec<FluxNote>,
-    notes_to_render: Vec<FluxNote>,
-    index: usize,
+    pub engine: FluxEngine,
+    noteset_len: usize,
+    hitset_len: usize,
+    miss_sound_len: usize,
+    cursorset_len: usize,
+    /// first beat and beat length in ms from the map's "offset" and "bpm" metadata
+    beats: Option<(f64, f64)>,
+    /// colors picked by the player or suggested by the map, empty leaves them to the noteset
+    palette: Vec<FluxColor>,
+    /// ms missed notes stay, 0 for no pushback
+    pushback: f64,
+    pushed: Vec<FluxPushedNote>,
 }
 
 impl FluxNotesManager {
     pub fn new() -> Self {
         Self {
-            notes: vec![],
-            notes_to_render: vec![],
-            index: 0,
+            engine: FluxEngine::empty(),
+            noteset_len: 1,
+            hitset_len: 1,
+            miss_sound_len: 1,
+            cursorset_len: 1,
+            beats: None,
+            palette: vec![],
+            pushback: 0.0,
+            pushed: vec![],
         }
     }
 
     pub fn reset(&mut self) {
-        self.notes = vec![];
-        self.notes_to_render = vec![];
-        self.index = 0;
+        self.engine = FluxEngine::empty();
+        self.pushed.clear();
     }
 
-    pub fn load_maps(&mut self, config: &FluxConfig, map: &FluxMap, noteset: &FluxNoteset, hitset: &FluxHitset, cursorset: &FluxCursorset) {
-        for (i, note) in map.map_data.split(",").enumerate() {
-            if i < 1 {
-                continue; // ignore roblox id
-            }
-
-            let note_nnl = note.replace("\r", "").replace("\n", "");
-            let note_data: Vec<&str> = note_nnl.split("|").collect();
-            
-            let note_ms: u64 = note_data[2].parse::<u64>().expect("Invalid map data.");
-            
-            self.notes.push(FluxNote {
-                x: note_data[0].parse::<f32>().expect("Invalid map data."),
-                y: note_data[1].parse::<f32>().expect("Invalid map data."),
-                z: MAX_AR_AD as f32,
-                ms: note_ms,
-                index: i as u32,
-                spawn_time: (note_ms as f32 - (config.note.approach_time * config.audio.speed as f32)) as u64,
-                noteset_index: i % noteset.textures.len(),
-                hitset_index: i % hitset.sounds.len(),
-                cursorset_index: i % cursorset.textures.len(),
-                hitsound_played: false,
-            });
-        }
+    pub fn load_maps(&mut self, config: &FluxConfig, map: &FluxMap, difficulty: &str, noteset: &FluxNoteset, hitset: &FluxHitset, cursorset: &FluxCursorset) {
+        let notes = match map.difficulties.get(difficulty) {
+            Some(notes) => notes,
+            None => return,
+        };
+        // notes cycle through the sets in order
+        self.noteset_len = noteset.textures.len().max(1);
+        self.hitset_len = hitset.sounds.len().max(1);
+        self.miss_sound_len = hitset.miss_sounds.len().max(1);
+        self.cursorset_len = cursorset.textures.len().max(1);
+        let meta_f64 = |key: &str| map.get_meta_string(key).and_then(|v| v.trim().parse::<f64>().ok());
+        self.beats = meta_f64("bpm")
+            .filter(|bpm| *bpm > 0.0)
+            .map(|bpm| (meta_f64("offset").unwrap_or(0.0), 60000.0 / bpm));
+        self.palette = if !config.note.colors.is_empty() {
+            config.note.colors.clone()
+        } else if config.note.map_colors {
+            map.get_meta_string("colors").and_then(|c| {
+                let palette = parse_palette(&c);
+                if palette.is_none() {
+                    log::warn!("Ignoring bad \"colors\" metadata in map: {}", c);
+                }
+                palette
+            }).unwrap_or_default()
+        } else {
+            vec![]
+        };
+        self.pushback = config.note.pushback.max(0.0) as f64;
+        self.pushed.clear();
+        let mut engine_config = config.engine();
+        engine_config.health = config.health.for_map(map, difficulty);
+        self.engine = FluxEngine::new(engine_config, notes);
     }
 
-    pub fn update_index(&mut self, time_manager: FluxTimeManager) {
-        if time_manager.paused {
-            return;
-        }
-        
-        if time_manager.song_timer.current_ms < self.notes[self.index].spawn_time {
-            return;
-        }
-
-        if self.index + 1 >= self.notes.len() {
-            return;
-        }
-
-        self.notes_to_render.push(self.notes[self.index].clone());
-
-        self.index += 1;
-        return;
+    /// rebuilds the notes on screen for a jump to `now`
+    pub fn seek(&mut self, now: f64) {
+        self.engine.seek(now);
+        self.pushed.clear();
     }
 
-    pub fn move_notes(&mut self, app: &App, stats_manager: &mut FluxStatsManager, time_manager: &FluxTimeManager, config: &FluxConfig, audio_manager: &mut FluxAudioManager, hitset: &mut FluxHitset, cursorset: &mut FluxCursorset) {
-        let mut remove: i32 = -1;
-        let mut hit = false;
+    /// true once every note has been spawned and judged
+    pub fn finished(&self) -> bool {
+        self.engine.finished()
+    }
 
-        for (i, mut note) in self.notes_to_render.iter_mut().enumerate() {
-            let st = note.ms as f32 - config.note.approach_time;
-            let t = (note.ms as i64 - time_manager.song_timer.current_ms as i64) as f32 / (note.ms as f32 - st);
-            note.z = t as f32 * config.note.ad;
-            if note.z < 0.5 {
-                remove = i as i32;
-            }
-            if note.z <= 1.0 && !note.hitsound_played {
-                audio_manager.play_hitsound(hitset);
-                note.hitsound_played = true;
-            }
-            if note.z >= 0.5 && note.z <= 1.0 {
-                if Rect::from_xy_wh(
-                    Vec2::new((-note.x + 1.0) * ((config.misc.play_area_width/3.0)), (note.y - 1.0) * ((config.misc.play_area_height/3.0))),
-                    Vec2::new((config.misc.play_area_width/3.0) * config.note.hitbox, (config.misc.play_area_height/3.0) * config.note.hitbox)
-                ).contains(Vec2::new(app.mouse.x, app.mouse.y)) {
-                    remove = i as i32;
-                    hit = true;
+    /// `cursor` is relative to the play area, (0,0) is the center and ±0.5 the edges.
+    /// returns what happened so the caller can show it
+    pub fn move_notes(&mut self, now: f64, cursor: Vec2, stats: &mut FluxStats, audio_manager: &mut FluxAudioManager, hitset: &mut FluxHitset, cursorset: &mut FluxCursorset) -> Vec<FluxNoteEvent> {
+        let events = self.engine.step(now, cursor.x, cursor.y, stats);
+        let pushback = self.pushback;
+        self.pushed.retain(|p| now - p.missed_at < pushback);
+        for event in events.iter() {
+            match *event {
+                FluxNoteEvent::Hitsound { index } => {
+                    hitset.index = index as usize % self.hitset_len;
+                    audio_manager.play_hitsound(hitset);
+                }
+                FluxNoteEvent::Judged { index, judgement, x, y, ms } => {
+                    cursorset.index = index as usize % self.cursorset_len;
+                    hitset.index = index as usize % self.hitset_len;
+                    if judgement == FluxJudgement::Miss {
+                        hitset.miss_index = stats.notes_missed.saturating_sub(1) % self.miss_sound_len;
+                        audio_manager.play_miss_sound(hitset);
+                        if self.pushback > 0.0 {
+                            self.pushed.push(FluxPushedNote { index, x, y, ms, missed_at: now });
+                        }
+                    }
                 }
             }
         }
-        if remove != -1 {
-            if hit {
-                stats_manager.notes_hit += 1;
-            } else {
-                stats_manager.notes_missed += 1;
-            }
-            cursorset.index = self.notes_to_render[remove as usize].cursorset_index;
-            hitset.index = self.notes_to_render[remove as usize].hitset_index;
-            stats_manager.note_total += 1;
-            self.notes_to_render.remove(remove as usize);
-        }
+        events
+    }
+
+    /// the color of the note `index` at `ms`
+    pub fn note_color(&self, noteset: &FluxNoteset, index: u32, ms: f64) -> FluxColor {
+        let beat = self.beats.map(|(offset, length)| ((ms - offset) / length).floor().max(0.0) as u32);
+        noteset.color(&self.palette, index, beat)
     }
-    
-    pub fn render(&mut self, app: &App, draw: Draw, noteset: &FluxNoteset, config: &FluxConfig, time_manager: &FluxTimeManager, notes_manager: &FluxNotesManager) {
-        for (i, note) in self.notes_to_render.iter().rev().enumerate() {
-            draw.texture(&noteset.textures[note.noteset_index])
-                .width((config.misc.play_area_width/3.0) / note.z)
-                .height((config.misc.play_area_height/3.0) / note.z)
-                .x((-note.x + 1.0) * ((config.misc.play_area_width/3.0) / (note.z)))
-                .y((note.y - 1.0) * ((config.misc.play_area_height/3.0) / (note.z)));
+
+    /// the texture of the note `index`
+    pub fn note_texture<'a>(&self, noteset: &'a FluxNoteset, index: u32) -> Option<&'a wgpu::Texture> {
+        noteset.textures.get(index as usize % self.noteset_len)
+    }
+
+    pub fn render(&self, app: &App, draw: Draw, noteset: &FluxNoteset, config: &FluxConfig, time_manager: &FluxTimeManager, camera: &FluxCamera) {
+        let mods = &self.engine.config.mods;
+        let now = time_manager.song_timer.current_ms;
+        for (i, note) in self.engine.active_notes().iter().rev().enumerate() {
+            let opacity = mods.opacity(note.z, self.engine.config.ad) * config.note.fade_opacity(note.z);
+            // pushback stops notes at the hit plane until they're judged
+            let depth = if self.pushback > 0.0 { note.depth().max(HIT_PLANE) } else { note.depth() };
+            self.draw_note(&draw, noteset, camera, note.index, note.x, note.y, depth, note.ms, opacity);
 
             if config.misc.debug {
                 draw.text(
-                    &format!("i: {}, a: {}, ms: {}, nms: {}, x: {}, y: {}, z: {:.1}, st: {:.2}", 
-                        notes_manager.index, 
-                        note.index, 
-                        time_manager.song_timer.current_ms, 
-                        note.ms, 
-                        note.x, 
-                        note.y, 
-                        note.z, 
+                    &format!("i: {}, a: {}, ms: {:.2}, nms: {}, x: {}, y: {}, z: {:.1}, st: {:.2}",
+                        self.engine.spawned(),
+                        note.index,
+                        time_manager.song_timer.current_ms,
+                        note.ms,
+                        note.x,
+                        note.y,
+                        note.z,
                         note.spawn_time))
                     .color(WHITE)
                     .x(10.0)
@@ -149,5 +188,20 @@ impl FluxNotesManager {
                     .left_justify();
             }
         }
+        for pushed in self.pushed.iter() {
+            let opacity = 1.0 - ((now - pushed.missed_at) / self.pushback).clamp(0.0, 1.0) as f32;
+            self.draw_note(&draw, noteset, camera, pushed.index, pushed.x, pushed.y, HIT_PLANE, pushed.ms, opacity);
+        }
     }
-}
\ No newline at end of file
+
+    fn draw_note(&self, draw: &Draw, noteset: &FluxNoteset, camera: &FluxCamera, index: u32, x: f32, y: f32, depth: f32, ms: f64, opacity: f32) {
+        let texture = self.note_texture(noteset, index);
+        let r = camera.note_rect(x, y, depth);
+        if let (Some(texture), Some(r)) = (texture.filter(|_| opacity > 0.0), r) {
+            let rect = noteset.manifest.sprite.rect(r.x(), r.y(), r.w(), r.h());
+            let mut color = self.note_color(noteset, index, ms).rgba();
+            color.alpha *= opacity;
+            draw_tinted(draw, texture, rect, color);
+        }
+    }
+}
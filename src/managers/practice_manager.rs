@@ -0,0 +1,85 @@
+use nannou::prelude::*;
+
+use crate::core::config::FluxConfig;
+
+// how far the seek keys jump
+pub const PRACTICE_SEEK_MS: f64 = 5000.0;
+pub const PRACTICE_SPEED_STEP: f64 = 0.05;
+
+fn format_ms(ms: f64) -> String {
+    let ms = ms.max(0.0) as u64;
+    format!("{}:{:02}.{}", ms / 60000, (ms / 1000) % 60, (ms % 1000) / 100)
+}
+
+/// start point and A/B loop of a practice play, nothing is recorded while practicing
+#[derive(Clone, Default)]
+pub struct FluxPracticeManager {
+    pub enabled: bool,
+    /// where the play started, restarts go here when there is no loop
+    pub start_ms: f64,
+    pub loop_a: Option<f64>,
+    pub loop_b: Option<f64>,
+}
+
+impl FluxPracticeManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(&mut self, start_ms: f64) {
+        *self = Self {
+            enabled: true,
+            start_ms: start_ms.max(0.0),
+            loop_a: None,
+            loop_b: None,
+        };
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// sets the loop start, a loop end before it is dropped
+    pub fn set_a(&mut self, ms: f64) {
+        self.loop_a = Some(ms);
+        if self.loop_b.map_or(false, |b| b <= ms) {
+            self.loop_b = None;
+        }
+    }
+
+    /// sets the loop end, ignored unless it is after the loop start
+    pub fn set_b(&mut self, ms: f64) {
+        if ms > self.restart_point() {
+            self.loop_b = Some(ms);
+        }
+    }
+
+    pub fn clear_loop(&mut self) {
+        self.loop_a = None;
+        self.loop_b = None;
+    }
+
+    /// where a restart seeks to
+    pub fn restart_point(&self) -> f64 {
+        self.loop_a.unwrap_or(self.start_ms)
+    }
+
+    /// true once the song passed the end of the loop
+    pub fn loop_due(&self, now: f64) -> bool {
+        self.enabled && self.loop_b.map_or(false, |b| now >= b)
+    }
+
+    pub fn render(&self, app: &App, draw: Draw, config: &FluxConfig, now: f64) {
+        if !self.enabled {
+            return;
+        }
+        let a = self.loop_a.map_or(String::from("-"), format_ms);
+        let b = self.loop_b.map_or(String::from("-"), format_ms);
+        draw.text(&format!("PRACTICE {} | A {} | B {} | {:.2}x\nR restart | [ ] set loop | \\ clear | Left/Right seek | Up/Down speed",
+                format_ms(now), a, b, config.audio.speed))
+            .color(rgba(1.0, 1.0, 1.0, 0.6))
+            .font_size(16)
+            .y(-(config.misc.play_area_height / 2.0) - 40.0)
+            .width(app.window_rect().w());
+    }
+}
@@ -0,0 +1,155 @@
+use std::{path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use flux_engine::{health::FluxHealthConfig, mods::FluxMods};
+use flux_map::{FluxMap, replay::{FluxReplay, FluxReplayFrame, FluxReplaySettings}};
+
+use crate::core::{config::{FluxApproachMode, FluxConfig}, constants::{REPLAY_DIR, REPLAY_EXT}};
+
+/// marks replays that were played by autoplay
+pub const AUTOPLAY_MOD: &str = "autoplay";
+
+/// records the cursor while playing, or feeds a replay's cursor back in
+pub struct FluxReplayManager {
+    replay: Option<FluxReplay>,
+    pub playing_back: bool,
+    playback_index: usize,
+    /// the last finished or watched replay
+    pub last: Option<FluxReplay>,
+    /// where `last` was saved, None for watched replays
+    pub last_path: Option<PathBuf>,
+    /// whether `last` was watched rather than recorded
+    watched: bool,
+}
+
+impl FluxReplayManager {
+    pub fn new() -> Self {
+        Self {
+            replay: None,
+            playing_back: false,
+            playback_index: 0,
+            last: None,
+            last_path: None,
+            watched: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.replay = None;
+        self.playing_back = false;
+        self.playback_index = 0;
+        self.watched = false;
+    }
+
+    /// `health` is the config's health with the map's rates applied
+    pub fn settings(config: &FluxConfig, health: &FluxHealthConfig) -> FluxReplaySettings {
+        FluxReplaySettings {
+            ar: config.note.approach_rate(),
+            ad: config.note.ad,
+            hitbox: config.note.hitbox,
+            speed: config.audio.speed,
+            windows: [config.judgement.perfect, config.judgement.great, config.judgement.good],
+            health: [health.miss_drain, health.hit_regen],
+        }
+    }
+
+    /// makes `config` play the same way the replay was played
+    pub fn apply_settings(config: &mut FluxConfig, replay: &FluxReplay) {
+        let settings = &replay.settings;
+        config.note.approach = FluxApproachMode::Rate;
+        config.note.ar = settings.ar;
+        config.note.ad = settings.ad;
+        config.note.hitbox = settings.hitbox;
+        config.audio.speed = settings.speed;
+        config.judgement.perfect = settings.windows[0];
+        config.judgement.great = settings.windows[1];
+        config.judgement.good = settings.windows[2];
+        config.health.miss_drain = settings.health[0];
+        config.health.hit_regen = settings.health[1];
+        config.mods = FluxMods::from_ids(&replay.mods);
+    }
+
+    pub fn start_recording(&mut self, map: &FluxMap, difficulty: &str, config: &FluxConfig, autoplay: bool) {
+        self.reset();
+        let mut replay = FluxReplay::new(map.content_hash(), difficulty.to_string(), Self::settings(config, &config.health.for_map(map, difficulty)));
+        replay.mods = config.mods.ids();
+        if autoplay {
+            replay.mods.push(String::from(AUTOPLAY_MOD));
+        }
+        self.replay = Some(replay);
+    }
+
+    pub fn start_playback(&mut self, replay: FluxReplay) {
+        self.reset();
+        self.replay = Some(replay);
+        self.playing_back = true;
+    }
+
+    /// stores the cursor position the notes were judged against, relative to the play area
+    pub fn record(&mut self, frame: FluxReplayFrame) {
+        if self.playing_back {
+            return;
+        }
+        if let Some(replay) = self.replay.as_mut() {
+            replay.frames.push(frame);
+        }
+    }
+
+    /// every replay frame up to `ms` that hasn't been played yet
+    pub fn frames_until(&mut self, ms: f64) -> Vec<FluxReplayFrame> {
+        let replay = match self.replay.as_ref() {
+            Some(replay) if self.playing_back => replay,
+            _ => return vec![],
+        };
+        let mut frames = vec![];
+        while self.playback_index < replay.frames.len() && replay.frames[self.playback_index].ms <= ms {
+            frames.push(replay.frames[self.playback_index]);
+            self.playback_index += 1;
+        }
+        frames
+    }
+
+    /// ends the current play, a recorded replay is saved to the replays directory
+    pub fn finish(&mut self) {
+        let mut replay = match self.replay.take() {
+            Some(replay) => replay,
+            None => return,
+        };
+        self.last_path = None;
+        if !self.playing_back {
+            replay.timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            match Self::save(&replay) {
+                Ok(path) => self.last_path = Some(path),
+                Err(e) => log::error!("Failed to save replay: {}", e),
+            }
+        }
+        self.watched = self.playing_back;
+        self.playing_back = false;
+        self.last = Some(replay);
+    }
+
+    /// whether the last finished play was a watched replay
+    pub fn watched(&self) -> bool {
+        self.watched
+    }
+
+    /// the replay that is or just was being watched
+    pub fn watched_replay(&self) -> Option<FluxReplay> {
+        if self.playing_back {
+            self.replay.clone()
+        } else if self.watched {
+            self.last.clone()
+        } else {
+            None
+        }
+    }
+
+    fn save(replay: &FluxReplay) -> Result<PathBuf, flux_map::replay::FluxReplayError> {
+        std::fs::create_dir_all(REPLAY_DIR)?;
+        let name = format!("{}_{}_{}", &replay.map_hash_hex()[..16], replay.difficulty, replay.timestamp);
+        // difficulty names come from the map, keep the file name safe
+        let name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '-' }).collect();
+        let path = PathBuf::from(REPLAY_DIR).join(name).with_extension(REPLAY_EXT);
+        replay.save(path.clone())?;
+        Ok(path)
+    }
+}
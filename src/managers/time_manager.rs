@@ -1,53 +1,110 @@
 use crate::core::timer::FluxTimer;
 
+// time that has to pass between pausing and unpausing
+const PAUSE_COOLDOWN_MS: f64 = 1000.0;
+// countdown between asking to resume and the song playing again
+pub const RESUME_COUNTDOWN_MS: f64 = 3000.0;
+
 pub struct FluxTimeManager {
+    /// song position in ms, follows the audio clock
     pub song_timer: FluxTimer,
+    /// time since the game was paused
     pub pause_timer: FluxTimer,
+    /// time since the game was started or unpaused
     pub unpause_timer: FluxTimer,
+    /// time since resuming was asked for
+    pub resume_timer: FluxTimer,
     pub paused: bool,
-    pub paused_total: u64,
+    /// counting down to resume
+    pub resuming: bool,
 }
 
 impl FluxTimeManager {
     pub fn new(speed: f64, offset: i64)  -> Self {
         Self {
             song_timer: FluxTimer::new(speed, offset),
-            pause_timer: FluxTimer::new(speed, offset),
-            unpause_timer: FluxTimer::new(speed, offset),
+            pause_timer: FluxTimer::new(1.0, 0),
+            unpause_timer: FluxTimer::new(1.0, 0),
+            resume_timer: FluxTimer::new(1.0, 0),
             paused: false,
-            paused_total: 0,
+            resuming: false,
         }
     }
 
     pub fn start(&mut self) {
-        self.song_timer.start_with_speed();
+        self.song_timer.start();
         self.unpause_timer.start();
     }
 
-    pub fn update_paused_total(&mut self) {
-        self.paused_total += self.pause_timer.current_ms;
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.song_timer.pause();
+        self.pause_timer.start();
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.resuming = false;
+        self.song_timer.resume();
+        self.unpause_timer.start();
+    }
+
+    /// starts the countdown to resume, `resume_due` says when it is over
+    pub fn start_resume(&mut self) {
+        self.resuming = true;
+        self.resume_timer.start();
+    }
+
+    pub fn cancel_resume(&mut self) {
+        self.resuming = false;
+        self.resume_timer.reset();
+    }
+
+    pub fn resume_due(&self) -> bool {
+        self.resuming && self.resume_timer.current_ms >= RESUME_COUNTDOWN_MS
     }
 
-    pub fn update(&mut self) {
+    /// ms left until the song resumes, None if not resuming
+    pub fn resume_countdown_ms(&self) -> Option<f64> {
+        self.resuming.then(|| (RESUME_COUNTDOWN_MS - self.resume_timer.current_ms).max(0.0))
+    }
+
+    pub fn can_toggle_pause(&self) -> bool {
+        if self.paused {
+            self.pause_timer.current_ms >= PAUSE_COOLDOWN_MS
+        } else {
+            self.unpause_timer.current_ms >= PAUSE_COOLDOWN_MS
+        }
+    }
+
+    /// `song_position_ms` is where the audio currently is, if a song is playing
+    pub fn update(&mut self, song_position_ms: Option<f64>) {
         if self.paused {
             self.pause_timer.update();
+            self.resume_timer.update();
             return;
         }
-        self.song_timer.update_with_offset_and_speed(self.paused_total as i64);
+        self.song_timer.update();
+        if let Some(position) = song_position_ms {
+            self.song_timer.sync(position);
+        }
         self.unpause_timer.update();
     }
 
     pub fn set_speed(&mut self, speed: f64) {
         self.song_timer.set_speed(speed);
-        self.pause_timer.set_speed(speed);
-        self.unpause_timer.set_speed(speed);
+    }
+
+    pub fn seek(&mut self, ms: f64) {
+        self.song_timer.seek(ms);
     }
 
     pub fn reset(&mut self) {
         self.song_timer.reset();
         self.pause_timer.reset();
         self.unpause_timer.reset();
+        self.resume_timer.reset();
         self.paused = false;
-        self.paused_total = 0;
+        self.resuming = false;
     }
-}
\ No newline at end of file
+}
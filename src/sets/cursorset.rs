@@ -4,9 +4,12 @@ use std::path::PathBuf;
 use nannou::wgpu;
 use nannou::prelude::*;
 
+use super::manifest::{FluxCursorsetManifest, load_manifest, set_files};
+
 pub struct FluxCursorset {
     pub textures: Vec<wgpu::Texture>,
     pub index: usize,
+    pub manifest: FluxCursorsetManifest,
 }
 
 impl FluxCursorset {
@@ -14,20 +17,35 @@ impl FluxCursorset {
         Self {
             textures: vec![],
             index: 0,
+            manifest: FluxCursorsetManifest::default(),
         }
     }
 
     pub fn load_from_path(&mut self, app: &App, path: PathBuf) {
         self.textures = vec![];
-        for e in Path::new(&path).read_dir().unwrap() {
-            let path = e.unwrap().path();
-            if let Some(ext) = path.extension() {
-                if ext == "png" || ext == "jpg" || ext == "jpeg" {
+        self.index = 0;
+        self.manifest = load_manifest(&path).unwrap_or_default();
+        let files = if self.manifest.textures.is_empty() {
+            set_files(Path::new(&path), &["png", "jpg", "jpeg"])
+        } else {
+            self.manifest.textures.iter().map(|t| path.join(t)).collect()
+        };
+        for path in files {
+            match wgpu::Texture::from_path(app, path.clone()) {
+                Ok(tex) => {
                     println!("Loaded cursor image: {:?}", path);
-                    let tex = wgpu::Texture::from_path(app, String::from(path.to_str().unwrap())).unwrap();
                     self.textures.push(tex);
                 }
+                Err(e) => log::warn!("Failed to load cursor image {:?}: {}", path, e),
             }
         }
     }
-}
\ No newline at end of file
+
+    /// the texture for the last hit note, None if the set has no textures
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        if self.textures.is_empty() {
+            return None;
+        }
+        self.textures.get(self.index % self.textures.len())
+    }
+}
@@ -1,31 +1,50 @@
 use std::path::{PathBuf, Path};
 
-use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
+use kira::{sound::static_sound::{StaticSoundData, StaticSoundSettings}, Volume};
+
+use super::manifest::{FluxHitsetManifest, load_manifest, set_files};
 
 pub struct FluxHitset {
     pub sounds: Vec<StaticSoundData>,
+    pub miss_sounds: Vec<StaticSoundData>,
     pub index: usize,
+    pub miss_index: usize,
+}
+
+fn load_sounds(files: Vec<PathBuf>, volume: f64) -> Vec<StaticSoundData> {
+    let mut sounds = vec![];
+    for path in files {
+        match StaticSoundData::from_file(&path, StaticSoundSettings::default().volume(Volume::Amplitude(volume))) {
+            Ok(sound) => {
+                println!("Loaded hitsound {:?}", path);
+                sounds.push(sound);
+            }
+            Err(e) => log::warn!("Failed to load hitsound {:?}: {}", path, e),
+        }
+    }
+    sounds
 }
 
 impl FluxHitset {
     pub fn new() -> Self {
         Self {
             sounds: vec![],
+            miss_sounds: vec![],
             index: 0,
+            miss_index: 0,
         }
     }
 
     pub fn load_from_path(&mut self, path: PathBuf) {
-        self.sounds = vec![];
-        for e in Path::new(&path).read_dir().unwrap() {
-            let path = e.unwrap().path();
-            if let Some(ext) = path.extension() {
-                if ext == "mp3" || ext == "wav" || ext == "ogg" {
-                    println!("Loaded hitsound {:?}", path);
-                    let sound = StaticSoundData::from_file(path, StaticSoundSettings::default());
-                    self.sounds.push(sound.expect("Failed to open hitset"));
-                }
-            }
-        }
+        let manifest: FluxHitsetManifest = load_manifest(&path).unwrap_or_default();
+        let hit = if manifest.hit.is_empty() {
+            set_files(Path::new(&path), &["mp3", "wav", "ogg"])
+        } else {
+            manifest.hit.iter().map(|s| path.join(s)).collect()
+        };
+        self.sounds = load_sounds(hit, manifest.volume);
+        self.miss_sounds = load_sounds(manifest.miss.iter().map(|s| path.join(s)).collect(), manifest.volume);
+        self.index = 0;
+        self.miss_index = 0;
     }
-}
\ No newline at end of file
+}
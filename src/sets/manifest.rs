@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use nannou::prelude::*;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+
+/// optional file in a set directory that describes how the set is used
+pub const MANIFEST_FILE: &str = "skin.toml";
+
+/// a color written as "#rrggbb" or "#rrggbbaa"
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FluxColor(pub [f32; 4]);
+
+impl FluxColor {
+    pub const WHITE: FluxColor = FluxColor([1.0; 4]);
+
+    pub fn rgba(&self) -> LinSrgba {
+        let [r, g, b, a] = self.0;
+        lin_srgba(r, g, b, a)
+    }
+
+    /// both colors multiplied together
+    pub fn tint(&self, other: &FluxColor) -> FluxColor {
+        let mut c = self.0;
+        for (c, o) in c.iter_mut().zip(other.0) {
+            *c *= o;
+        }
+        FluxColor(c)
+    }
+
+    /// `self` at 0 to `other` at 1
+    pub fn lerp(&self, other: &FluxColor, t: f32) -> FluxColor {
+        let mut c = self.0;
+        for (c, o) in c.iter_mut().zip(other.0) {
+            *c += (o - *c) * t;
+        }
+        FluxColor(c)
+    }
+}
+
+impl Default for FluxColor {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl TryFrom<String> for FluxColor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let hex = s.trim().trim_start_matches('#');
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return Err(format!("bad color '{}', expected #rrggbb or #rrggbbaa", s));
+        }
+        let mut c = [1.0; 4];
+        for (i, c) in c.iter_mut().enumerate().take(hex.len() / 2) {
+            let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| format!("bad color '{}'", s))?;
+            *c = byte as f32 / 255.0;
+        }
+        Ok(FluxColor(c))
+    }
+}
+
+impl From<FluxColor> for String {
+    fn from(c: FluxColor) -> Self {
+        let [r, g, b, a] = c.0.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+/// colors in a list like "#ff0000, #00ffff", None if any of them is bad
+pub fn parse_palette(s: &str) -> Option<Vec<FluxColor>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(|c| FluxColor::try_from(c.to_string()).ok())
+        .collect()
+}
+
+/// what the note colors cycle with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FluxColorCycle {
+    /// the next color for every note
+    #[default]
+    Note,
+    /// the next color for every beat, needs "bpm" metadata in the map
+    Beat,
+}
+
+/// how a texture sits on its position
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxSpriteManifest {
+    pub tint: FluxColor,
+    pub scale: f32,
+    /// point of the texture on the position, (0,0) is the top left and (1,1) the bottom right
+    pub anchor: [f32; 2],
+}
+
+impl Default for FluxSpriteManifest {
+    fn default() -> Self {
+        Self {
+            tint: FluxColor::WHITE,
+            scale: 1.0,
+            anchor: [0.5, 0.5],
+        }
+    }
+}
+
+impl FluxSpriteManifest {
+    /// the rect of a `w`x`h` texture anchored at `x`,`y`
+    pub fn rect(&self, x: f32, y: f32, w: f32, h: f32) -> Rect {
+        let (w, h) = (w * self.scale, h * self.scale);
+        Rect::from_x_y_w_h(
+            x + (0.5 - self.anchor[0]) * w,
+            y + (self.anchor[1] - 0.5) * h,
+            w,
+            h)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxNotesetManifest {
+    /// texture files in the order notes use them
+    pub textures: Vec<String>,
+    /// colors multiplied over the textures in turn, none keeps them as they are
+    pub colors: Vec<FluxColor>,
+    pub cycle: FluxColorCycle,
+    #[serde(flatten)]
+    pub sprite: FluxSpriteManifest,
+}
+
+/// how the cursor trail is drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FluxTrailStyle {
+    /// faded copies of the cursor texture
+    #[default]
+    Ghosts,
+    /// a solid band through the trail points in the tint color
+    Ribbon,
+}
+
+/// when the cursor trail adds a point
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FluxTrailSpawn {
+    /// `rate` points a second while the cursor moves
+    #[default]
+    Rate,
+    /// a point every `spacing` pixels the cursor moves
+    Distance,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxTrailManifest {
+    pub enabled: bool,
+    pub style: FluxTrailStyle,
+    pub spawn: FluxTrailSpawn,
+    /// points a second for the rate spawn
+    pub rate: f32,
+    /// pixels between points for the distance spawn
+    pub spacing: f32,
+    /// most points the trail keeps
+    pub length: usize,
+    /// how long a point stays, in ms
+    pub lifetime: f32,
+    /// opacity of the newest point, older ones fade towards 0
+    pub opacity: f32,
+    /// size of the oldest point relative to the cursor, newer ones grow towards 1
+    pub end_scale: f32,
+}
+
+impl Default for FluxTrailManifest {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            style: FluxTrailStyle::Ghosts,
+            spawn: FluxTrailSpawn::Rate,
+            rate: 120.0,
+            spacing: 8.0,
+            length: 32,
+            lifetime: 150.0,
+            opacity: 0.5,
+            end_scale: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxCursorsetManifest {
+    /// texture files in the order hits switch through them
+    pub textures: Vec<String>,
+    #[serde(flatten)]
+    pub sprite: FluxSpriteManifest,
+    pub trail: FluxTrailManifest,
+    /// degrees a second the cursor spins, negative spins clockwise
+    pub rotation: f32,
+    /// scale the cursor jumps to on a hit before easing back to 1
+    pub hit_scale: f32,
+    /// how long the hit scale takes to ease back, in ms
+    pub hit_scale_time: f32,
+}
+
+impl Default for FluxCursorsetManifest {
+    fn default() -> Self {
+        Self {
+            textures: vec![],
+            sprite: FluxSpriteManifest::default(),
+            trail: FluxTrailManifest::default(),
+            rotation: 0.0,
+            hit_scale: 1.0,
+            hit_scale_time: 100.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxHitsetManifest {
+    pub volume: f64,
+    /// played when a note reaches the hit plane, in turn
+    pub hit: Vec<String>,
+    /// played when a note is missed, in turn
+    pub miss: Vec<String>,
+}
+
+impl Default for FluxHitsetManifest {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            hit: vec![],
+            miss: vec![],
+        }
+    }
+}
+
+/// the manifest of the set in `dir`, None if the set has none or it can't be read
+pub fn load_manifest<T: DeserializeOwned>(dir: &Path) -> Option<T> {
+    let path = dir.join(MANIFEST_FILE);
+    let data = std::fs::read_to_string(&path).ok()?;
+    match toml::from_str(&data) {
+        Ok(manifest) => Some(manifest),
+        Err(e) => {
+            log::error!("Failed to parse {:?}, loading the set without it: {}", path, e);
+            None
+        }
+    }
+}
+
+/// files of the set in `dir` with one of `exts`, in directory order
+pub fn set_files(dir: &Path, exts: &[&str]) -> Vec<std::path::PathBuf> {
+    let mut files = vec![];
+    for e in dir.read_dir().unwrap() {
+        let path = e.unwrap().path();
+        if let Some(ext) = path.extension() {
+            if exts.iter().any(|e| ext == *e) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
@@ -1,3 +1,5 @@
 pub mod cursorset;
 pub mod hitset;
-pub mod noteset;
\ No newline at end of file
+pub mod manifest;
+pub mod noteset;
+pub mod skin;
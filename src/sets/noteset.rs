@@ -4,9 +4,12 @@ use std::path::PathBuf;
 use nannou::wgpu;
 use nannou::prelude::*;
 
+use super::manifest::{FluxNotesetManifest, FluxColor, FluxColorCycle, load_manifest, set_files};
+
 pub struct FluxNoteset {
     pub textures: Vec<wgpu::Texture>,
     pub index: usize,
+    pub manifest: FluxNotesetManifest,
 }
 
 impl FluxNoteset {
@@ -14,21 +17,40 @@ impl FluxNoteset {
         Self {
             textures: vec![],
             index: 0,
+            manifest: FluxNotesetManifest::default(),
         }
     }
 
     pub fn load_from_path(&mut self, app: &App, path: PathBuf) {
         self.textures = vec![];
-        for e in Path::new(&path).read_dir().unwrap() {
-            let path = e.unwrap().path();
-            if let Some(ext) = path.extension() {
-                if ext == "png" || ext == "jpg" || ext == "jpeg" {
+        self.manifest = load_manifest(&path).unwrap_or_default();
+        let files = if self.manifest.textures.is_empty() {
+            set_files(Path::new(&path), &["png", "jpg", "jpeg"])
+        } else {
+            self.manifest.textures.iter().map(|t| path.join(t)).collect()
+        };
+        for path in files {
+            match wgpu::Texture::from_path(app, path.clone()) {
+                Ok(tex) => {
                     println!("Loaded note texture: {:?}", path);
-                    let tex = wgpu::Texture::from_path(app, String::from(path.to_str().unwrap())).unwrap();
                     self.textures.push(tex);
                 }
+                Err(e) => log::warn!("Failed to load note texture {:?}: {}", path, e),
             }
         }
     }
-}
 
+    /// the color of a note from `palette`, or the noteset's own colors if it's empty.
+    /// `beat` is the beat the note is on if the map has a bpm
+    pub fn color(&self, palette: &[FluxColor], index: u32, beat: Option<u32>) -> FluxColor {
+        let colors = if palette.is_empty() { &self.manifest.colors } else { palette };
+        if colors.is_empty() {
+            return self.manifest.sprite.tint;
+        }
+        let i = match self.manifest.cycle {
+            FluxColorCycle::Beat => beat.unwrap_or(index),
+            FluxColorCycle::Note => index,
+        } as usize;
+        colors[i % colors.len()].tint(&self.manifest.sprite.tint)
+    }
+}
@@ -0,0 +1,242 @@
+use std::{path::{Path, PathBuf}, fs::File};
+
+use kira::{sound::static_sound::{StaticSoundData, StaticSoundSettings}, Volume};
+use nannou::{prelude::*, text::{self, Font}, wgpu};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use zip::ZipArchive;
+
+use crate::core::constants::{SKINS_DIR, SKIN_CACHE_DIR};
+use super::manifest::{FluxColor, MANIFEST_FILE, load_manifest};
+
+// set directories inside a skin, each one is optional
+const SKIN_NOTESET_DIR: &str = "notes";
+const SKIN_CURSORSET_DIR: &str = "cursor";
+const SKIN_HITSET_DIR: &str = "hitsounds";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxHudManifest {
+    pub text: FluxColor,
+    /// autoplay, practice and countdown text
+    pub accent: FluxColor,
+    pub border: FluxColor,
+    pub border_width: f32,
+    pub health_empty: FluxColor,
+    pub health_full: FluxColor,
+}
+
+impl Default for FluxHudManifest {
+    fn default() -> Self {
+        Self {
+            text: FluxColor::WHITE,
+            accent: FluxColor([1.0, 1.0, 0.0, 1.0]),
+            border: FluxColor::WHITE,
+            border_width: 2.0,
+            health_empty: FluxColor([1.0, 0.0, 0.0, 1.0]),
+            health_full: FluxColor([0.0, 1.0, 0.0, 1.0]),
+        }
+    }
+}
+
+/// how a missed note is shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FluxMissStyle {
+    /// an X in the miss color
+    #[default]
+    Cross,
+    /// the note in the miss color, shrinking as it fades
+    Shrink,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxEffectsManifest {
+    /// ms a hit ghost takes to fade
+    pub hit_time: f32,
+    /// size a hit ghost grows to, relative to the note
+    pub hit_scale: f32,
+    pub hit_opacity: f32,
+    pub miss_style: FluxMissStyle,
+    pub miss_color: FluxColor,
+    pub miss_time: f32,
+    /// popup colors for perfect, great, good and miss
+    pub judgement_colors: [FluxColor; 4],
+    pub popup_time: f32,
+    pub popup_size: u32,
+    /// the background pulses every this many combo, 0 never
+    pub pulse_combo: u32,
+    pub pulse_color: FluxColor,
+    pub pulse_time: f32,
+}
+
+impl Default for FluxEffectsManifest {
+    fn default() -> Self {
+        Self {
+            hit_time: 200.0,
+            hit_scale: 1.4,
+            hit_opacity: 0.6,
+            miss_style: FluxMissStyle::Cross,
+            miss_color: FluxColor([0.9, 0.25, 0.25, 1.0]),
+            miss_time: 400.0,
+            judgement_colors: [
+                FluxColor([0.35, 0.8, 1.0, 1.0]),
+                FluxColor([0.35, 0.9, 0.5, 1.0]),
+                FluxColor([0.95, 0.8, 0.3, 1.0]),
+                FluxColor([0.9, 0.25, 0.25, 1.0]),
+            ],
+            popup_time: 500.0,
+            popup_size: 24,
+            pulse_combo: 50,
+            pulse_color: FluxColor([1.0, 1.0, 1.0, 0.15]),
+            pulse_time: 300.0,
+        }
+    }
+}
+
+/// `skin.toml` at the root of a skin
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluxSkinManifest {
+    pub name: String,
+    pub author: String,
+    /// font file for the hud
+    pub font: Option<String>,
+    /// image drawn behind the play area
+    pub background: Option<String>,
+    /// how much of the background shows through, 0 hides it
+    pub background_opacity: f32,
+    /// replaces the miss sounds of the hitset
+    pub miss_sound: Option<String>,
+    pub miss_volume: f64,
+    pub hud: FluxHudManifest,
+    pub effects: FluxEffectsManifest,
+}
+
+impl Default for FluxSkinManifest {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            author: String::new(),
+            font: None,
+            background: None,
+            background_opacity: 0.3,
+            miss_sound: None,
+            miss_volume: 1.0,
+            hud: FluxHudManifest::default(),
+            effects: FluxEffectsManifest::default(),
+        }
+    }
+}
+
+/// A skin package, a directory or zip that can override any set and style the hud.
+/// Zips are extracted into the skin cache and loaded like directories.
+#[derive(Default)]
+pub struct FluxSkin {
+    /// the extracted skin directory, None for no skin
+    pub dir: Option<PathBuf>,
+    pub manifest: FluxSkinManifest,
+    pub font: Option<Font>,
+    pub background: Option<wgpu::Texture>,
+    pub miss_sound: Option<StaticSoundData>,
+}
+
+impl FluxSkin {
+    /// skin directories and zips in the skins directory
+    pub fn list() -> Vec<PathBuf> {
+        let mut skins: Vec<PathBuf> = match std::fs::read_dir(SKINS_DIR) {
+            Ok(dir) => dir.filter_map(|e| e.ok()).map(|e| e.path())
+                .filter(|p| p.file_name().map_or(false, |n| !n.to_string_lossy().starts_with('.')))
+                .filter(|p| p.is_dir() || p.extension().map_or(false, |e| e == "zip"))
+                .collect(),
+            Err(_) => vec![],
+        };
+        skins.sort();
+        skins
+    }
+
+    pub fn load(app: &App, path: &Path) -> Result<Self, FluxSkinError> {
+        let dir = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            Self::extract(path)?
+        };
+        let manifest: FluxSkinManifest = load_manifest(&dir).unwrap_or_default();
+        let font = manifest.font.as_ref().and_then(|f| {
+            text::font::from_file(dir.join(f))
+                .map_err(|e| log::warn!("Failed to load skin font {}: {}", f, e))
+                .ok()
+        });
+        let background = manifest.background.as_ref().and_then(|b| {
+            wgpu::Texture::from_path(app, dir.join(b))
+                .map_err(|e| log::warn!("Failed to load skin background {}: {}", b, e))
+                .ok()
+        });
+        let miss_sound = manifest.miss_sound.as_ref().and_then(|m| {
+            StaticSoundData::from_file(dir.join(m), StaticSoundSettings::default().volume(Volume::Amplitude(manifest.miss_volume)))
+                .map_err(|e| log::warn!("Failed to load skin miss sound {}: {}", m, e))
+                .ok()
+        });
+        log::info!("Loaded skin: {:?}", dir);
+        Ok(Self {
+            dir: Some(dir),
+            manifest,
+            font,
+            background,
+            miss_sound,
+        })
+    }
+
+    /// unpacks a zipped skin into the skin cache, reusing an earlier unpack if the zip hasn't changed
+    fn extract(path: &Path) -> Result<PathBuf, FluxSkinError> {
+        let stem = path.file_stem().ok_or(FluxSkinError::BadPath(path.to_path_buf()))?;
+        let to = PathBuf::from(SKIN_CACHE_DIR).join(stem);
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        if !(to.is_dir() && modified(&to) >= modified(path)) {
+            if to.exists() {
+                std::fs::remove_dir_all(&to)?;
+            }
+            std::fs::create_dir_all(&to)?;
+            let mut archive = ZipArchive::new(File::open(path)?)?;
+            archive.extract(&to)?;
+        }
+        // zips often wrap everything in one folder
+        if !to.join(MANIFEST_FILE).exists() {
+            let entries: Vec<PathBuf> = std::fs::read_dir(&to)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            if let [inner] = entries.as_slice() {
+                if inner.is_dir() {
+                    return Ok(inner.clone());
+                }
+            }
+        }
+        Ok(to)
+    }
+
+    /// the skin's own set in `name`, None if it doesn't override that set
+    fn set_dir(&self, name: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|d| d.join(name)).filter(|d| d.is_dir())
+    }
+
+    pub fn noteset_dir(&self) -> Option<PathBuf> {
+        self.set_dir(SKIN_NOTESET_DIR)
+    }
+
+    pub fn cursorset_dir(&self) -> Option<PathBuf> {
+        self.set_dir(SKIN_CURSORSET_DIR)
+    }
+
+    pub fn hitset_dir(&self) -> Option<PathBuf> {
+        self.set_dir(SKIN_HITSET_DIR)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FluxSkinError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("bad skin path {0:?}")]
+    BadPath(PathBuf),
+}
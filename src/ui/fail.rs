@@ -0,0 +1,36 @@
+use nannou_egui::{egui::{self, Color32, Label}, FrameCtx};
+
+use flux_engine::stats::FluxStats;
+
+pub enum FluxFailAction {
+    Retry,
+    Quit,
+}
+
+/// shown over the play area once health runs out
+pub struct FluxFailUI;
+
+impl FluxFailUI {
+    pub fn render(ctx: &FrameCtx, stats: &FluxStats) -> Option<FluxFailAction> {
+        let mut action = None;
+        egui::Window::new("Failed")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .title_bar(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.add(Label::new("FAILED").heading().text_color(Color32::RED));
+                ui.label(format!("Accuracy: {:.2}%", stats.accuracy()));
+                ui.label(format!("Notes: {}/{}", stats.notes_hit, stats.note_total));
+                ui.label(format!("Score: {}", stats.score));
+                ui.horizontal(|ui| {
+                    if ui.button("Retry").clicked() {
+                        action = Some(FluxFailAction::Retry);
+                    }
+                    if ui.button("Quit").clicked() {
+                        action = Some(FluxFailAction::Quit);
+                    }
+                });
+            });
+        action
+    }
+}
@@ -1,52 +1,105 @@
-use crate::{core::{config::FluxConfig, maploader::FluxMap}, managers::{stats_manager::FluxStatsManager, time_manager::FluxTimeManager}};
-use nannou::prelude::*;
+use flux_map::FluxMap;
+use flux_engine::{FluxEngine, judgement::FluxJudgement, stats::FluxStats};
+
+use crate::{core::{config::FluxConfig, camera::FluxCamera}, managers::time_manager::FluxTimeManager, sets::skin::FluxSkin};
+use nannou::{prelude::*, draw::{Drawing, primitive::Text}};
 pub struct FluxHud;
 
+/// hud text in the skin's text color and font
+pub fn text<'a>(draw: &'a Draw, skin: &FluxSkin, s: &str) -> Drawing<'a, Text> {
+    let text = draw.text(s).color(skin.manifest.hud.text.rgba());
+    match &skin.font {
+        Some(font) => text.font(font),
+        None => text,
+    }
+}
+
 impl FluxHud {
-    pub fn draw(app: &App, draw: Draw, map: &FluxMap, config: &FluxConfig, stats: &FluxStatsManager, time_manager: &FluxTimeManager) {
-        draw.text(&format!("{} - {}", map.artist, map.song_name))
-            .color(WHITE).
-            y(config.misc.play_area_height + 20.0)
+    /// the play area border on the hit plane, it turns with the camera
+    pub fn draw_border(draw: &Draw, skin: &FluxSkin, camera: &FluxCamera) {
+        let hud = &skin.manifest.hud;
+        if let Some(corners) = camera.play_area() {
+            draw.polyline()
+                .weight(hud.border_width)
+                .points_closed(corners)
+                .color(hud.border.rgba());
+        }
+    }
+
+    pub fn draw(app: &App, draw: Draw, map: &FluxMap, config: &FluxConfig, stats: &FluxStats, time_manager: &FluxTimeManager, engine: &FluxEngine, autoplay: bool, skin: &FluxSkin) {
+        let hud = &skin.manifest.hud;
+        text(&draw, skin, &format!("{} - {}", map.get_meta_string("artist").unwrap_or_default(), map.get_meta_string("song_name").unwrap_or_default()))
+            .y(config.misc.play_area_height + 20.0)
             .font_size(25)
             .width(app.window_rect().w() as f32);
         
         
-        draw.rect()
-            .width(config.misc.play_area_width as f32)
-            .height(config.misc.play_area_height as f32)
-            .x_y(0.0, 0.0)
-            .no_fill()
-            .stroke(WHITE)
-            .stroke_weight(2.0);
-        
-        draw.text(&format!("Notes:\n{}/{}", stats.notes_hit, stats.note_total))
+        text(&draw, skin, &format!("Score:\n{}", stats.score))
             .width(config.misc.play_area_width as f32)
             .x(config.misc.play_area_width + 40.0)
             .left_justify()
             .y(-(config.misc.play_area_height / 2.0) + 100.0)
-            .color(WHITE)
             .font_size(30);
         
-        draw.text(&format!("Accuracy:\n{:.2}%", (stats.notes_hit as f32 / stats.note_total as f32) * 100.0))
+        text(&draw, skin, &format!("Accuracy:\n{:.2}%", stats.accuracy()))
             .width(config.misc.play_area_width as f32)
             .x(config.misc.play_area_width + 40.0)
             .left_justify()
             .y(-(config.misc.play_area_height / 2.0) + 200.0)
-            .color(WHITE)
             .font_size(30);
         
-        draw.text(&format!("Misses: {}", stats.notes_missed))
+        text(&draw, skin, &format!("Combo: {}x (max {}x)", stats.combo, stats.max_combo))
             .width(config.misc.play_area_width as f32)
             .x(config.misc.play_area_width + 40.0)
             .left_justify()
             .y(-(config.misc.play_area_height / 2.0) + 300.0)
-            .color(WHITE)
             .font_size(30);
 
-        if time_manager.paused {
-            draw.text("PAUSED")
-                .color(RED)
-                .font_size(50)
+        let judgements = FluxJudgement::ALL.iter()
+            .map(|j| format!("{}: {}", j.name(), stats.judgements[j.index()]))
+            .collect::<Vec<String>>()
+            .join("\n");
+        text(&draw, skin, &format!("{}\nError: {:+.1}ms", judgements, stats.mean_error()))
+            .width(config.misc.play_area_width as f32)
+            .x(config.misc.play_area_width + 40.0)
+            .left_justify()
+            .y(-(config.misc.play_area_height / 2.0) + 450.0)
+            .font_size(20);
+
+        let mods = &engine.config.mods;
+        if !mods.is_empty() {
+            text(&draw, skin, &format!("{} ({:.2}x)", mods.names(), mods.score_multiplier()))
+                .width(config.misc.play_area_width as f32)
+                .x(config.misc.play_area_width + 40.0)
+                .left_justify()
+                .y(-(config.misc.play_area_height / 2.0) + 20.0)
+                .font_size(20);
+        }
+
+        // health bar along the left edge of the play area, fills from the bottom
+        let bar_height = config.misc.play_area_height * stats.health as f32;
+        let bar_x = -(config.misc.play_area_width / 2.0) - 20.0;
+        draw.rect()
+            .x_y(bar_x, 0.0)
+            .w_h(10.0, config.misc.play_area_height)
+            .color(rgba(1.0, 1.0, 1.0, 0.15));
+        draw.rect()
+            .x_y(bar_x, -(config.misc.play_area_height / 2.0) + bar_height / 2.0)
+            .w_h(10.0, bar_height)
+            .color(hud.health_empty.lerp(&hud.health_full, stats.health as f32).rgba());
+
+        if autoplay {
+            text(&draw, skin, "AUTOPLAY")
+                .color(hud.accent.rgba())
+                .font_size(30)
+                .y(config.misc.play_area_height / 2.0 + 30.0)
+                .width(app.window_rect().w());
+        }
+
+        if let Some(ms) = time_manager.resume_countdown_ms() {
+            text(&draw, skin, &format!("{}", (ms / 1000.0).ceil()))
+                .color(hud.accent.rgba())
+                .font_size(80)
                 .width(app.window_rect().w());
         }
     }
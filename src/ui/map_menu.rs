@@ -1,21 +1,146 @@
-use std::path::Path;
+use std::{path::{Path, PathBuf}, collections::HashMap, time::SystemTime};
 
-use nannou::App;
-use nannou_egui::{egui::{self, Button}, FrameCtx};
+use flux_engine::mods::{FluxMod, FluxMods};
+use flux_map::{FluxMap, hash_hex};
+use nannou::{App, Draw, wgpu, image};
+use nannou_egui::{egui::{self, Color32, Label, Sense}, FrameCtx};
 
-use crate::{core::{maploader::FluxMaploader, constants::MAP_DIR}, FluxState, Model};
+use crate::{core::{maploader::FluxMaploader, constants::MAP_DIR, history::FluxScore}, set_captured, FluxState, Model};
 
+const THUMBNAIL_SIZE: f32 = 64.0;
+const COVER_SIZE: f32 = 256.0;
+const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Clone)]
+pub struct FluxDifficultyInfo {
+    pub name: String,
+    pub note_count: usize,
+    pub length_ms: u32,
+    pub stars: f32,
+}
+
+#[derive(Clone)]
+pub struct FluxMapEntry {
+    pub path: String,
+    /// hex content hash, the key of the map's scores in the history
+    pub hash: String,
+    pub title: String,
+    pub artist: String,
+    pub mapper: String,
+    /// sorted from easiest to hardest
+    pub difficulties: Vec<FluxDifficultyInfo>,
+    pub added: SystemTime,
+}
+
+impl FluxMapEntry {
+    fn from_map(path: String, map: &FluxMap, hash: &[u8; 32]) -> Self {
+        let mut difficulties: Vec<FluxDifficultyInfo> = map.difficulties.iter().map(|(name, notes)| FluxDifficultyInfo {
+            name: name.clone(),
+            note_count: notes.len(),
+            length_ms: FluxMap::length_ms(notes),
+            stars: FluxMap::star_rating(notes),
+        }).collect();
+        difficulties.sort_by(|a, b| a.stars.total_cmp(&b.stars));
+        let added = std::fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+        let file_name = Path::new(&path).file_stem().unwrap().to_str().unwrap().to_string();
+        Self {
+            title: map.get_meta_string("song_name").filter(|t| !t.trim().is_empty()).unwrap_or(file_name),
+            artist: map.get_meta_string("artist").unwrap_or_default().trim().to_string(),
+            mapper: map.get_meta_string("mapper").unwrap_or_default().trim().to_string(),
+            difficulties,
+            added,
+            hash: hash_hex(hash),
+            path,
+        }
+    }
+
+    fn hardest(&self) -> Option<&FluxDifficultyInfo> {
+        self.difficulties.last()
+    }
+
+    fn matches(&self, search: &str) -> bool {
+        let fields = [&self.title, &self.artist, &self.mapper].map(|f| f.to_lowercase());
+        search.to_lowercase().split(" ").filter(|s| !s.is_empty()).all(|s| {
+            fields.iter().any(|f| f.contains(s))
+            || self.difficulties.iter().any(|d| d.name.to_lowercase().contains(s))
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum FluxMapSort {
+    #[default]
+    Title,
+    Artist,
+    Length,
+    Difficulty,
+    DateAdded,
+}
+
+impl FluxMapSort {
+    const ALL: [FluxMapSort; 5] = [FluxMapSort::Title, FluxMapSort::Artist, FluxMapSort::Length, FluxMapSort::Difficulty, FluxMapSort::DateAdded];
+
+    fn name(&self) -> &'static str {
+        match self {
+            FluxMapSort::Title => "Title",
+            FluxMapSort::Artist => "Artist",
+            FluxMapSort::Length => "Length",
+            FluxMapSort::Difficulty => "Difficulty",
+            FluxMapSort::DateAdded => "Date added",
+        }
+    }
+
+    fn sort(&self, maps: &mut Vec<FluxMapEntry>) {
+        match self {
+            FluxMapSort::Title => maps.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase())),
+            FluxMapSort::Artist => maps.sort_by(|a, b| a.artist.to_lowercase().cmp(&b.artist.to_lowercase())),
+            FluxMapSort::Length => maps.sort_by_key(|m| m.hardest().map_or(0, |d| d.length_ms)),
+            FluxMapSort::Difficulty => maps.sort_by(|a, b| {
+                a.hardest().map_or(0.0, |d| d.stars).total_cmp(&b.hardest().map_or(0.0, |d| d.stars))
+            }),
+            // newest first
+            FluxMapSort::DateAdded => maps.sort_by(|a, b| b.added.cmp(&a.added)),
+        }
+    }
+}
+
+fn format_length(ms: u32) -> String {
+    format!("{}:{:02}", (ms / 1000) / 60, (ms / 1000) % 60)
+}
+
+fn format_score(score: &FluxScore) -> String {
+    let mods = FluxMods::from_ids(&score.mods);
+    let mut text = format!("{} | {:.2}% | {}x | {} miss", score.score, score.accuracy, score.max_combo, score.misses);
+    if !mods.is_empty() {
+        text += &format!(" | {}", mods.names());
+    }
+    if score.speed != 1.0 {
+        text += &format!(" | {:.2}x speed", score.speed);
+    }
+    text + &format!(" | {}", score.age())
+}
+
+#[derive(Default)]
 pub struct FluxMapMenuUI {
-    maps: Vec<String>,
+    maps: Vec<FluxMapEntry>,
     map_search: String,
+    sort: FluxMapSort,
+    reverse_sort: bool,
+    selected: Option<String>,
+    selected_difficulty: String,
+    /// where practice starts, in seconds
+    practice_start: f64,
+    /// cover textures by map path, None if the map has no usable cover
+    covers: HashMap<String, Option<wgpu::Texture>>,
+    /// cover images read with the map info, until they're turned into textures
+    cover_data: HashMap<String, Vec<u8>>,
+    /// where egui left room for covers this frame, drawn underneath the ui in `draw`
+    cover_rects: Vec<(egui::Rect, String)>,
 }
 
 impl FluxMapMenuUI {
     pub fn new() -> Self {
-        Self {
-            maps: vec![],
-            map_search: String::from(""),
-        }
+        Self::default()
     }
 
     pub fn init(&mut self) {
@@ -23,49 +148,277 @@ impl FluxMapMenuUI {
             let path = file.unwrap().path();
             if let Some(ext) = path.extension() {
                 if ext == "flux" {
-                    self.maps.push(String::from(path.to_str().unwrap()));
+                    let path = String::from(path.to_str().unwrap());
+                    // the music is only hashed here and loaded once a map is played
+                    match FluxMap::open_info_hashed(PathBuf::from(&path)) {
+                        Ok((map, hash)) => {
+                            if let Some(image) = map.image_data.clone() {
+                                self.cover_data.insert(path.clone(), image);
+                            }
+                            self.maps.push(FluxMapEntry::from_map(path, &map, &hash));
+                        },
+                        Err(e) => log::warn!("Skipping map {}: {}", path, e),
+                    }
                 }
             }
         }
+        self.sort_maps();
+    }
+
+    pub fn refresh(&mut self) {
+        self.maps = vec![];
+        self.covers.clear();
+        self.cover_data.clear();
+        self.init();
+    }
+
+    pub fn select(&mut self, path: PathBuf) {
+        let path = String::from(path.to_str().unwrap());
+        self.selected_difficulty = self.maps.iter()
+            .find(|m| m.path == path)
+            .and_then(|m| m.difficulties.first())
+            .map(|d| d.name.clone())
+            .unwrap_or_default();
+        self.selected = Some(path);
+    }
+
+    fn sort_maps(&mut self) {
+        self.sort.sort(&mut self.maps);
+        if self.reverse_sort {
+            self.maps.reverse();
+        }
+    }
+
+    /// the path of the listed map with the hex content hash `hash`
+    pub fn find_by_hash(&self, hash: &str) -> Option<String> {
+        self.maps.iter().find(|m| m.hash == hash).map(|m| m.path.clone())
+    }
+
+    /// loads the cover of a map the first time it is shown
+    fn load_cover(&mut self, app: &App, path: &String) {
+        if self.covers.contains_key(path) {
+            return;
+        }
+        let cover = self.cover_data.remove(path)
+            .and_then(|data| image::load_from_memory(&data).ok())
+            .map(|img| wgpu::Texture::from_image(app, &img));
+        self.covers.insert(path.clone(), cover);
+    }
+
+    /// `practice` is where to start practicing in ms, None for a normal play
+    fn play_selected(&mut self, app: &App, model: &mut Model, autoplay: bool, practice: Option<f64>) {
+        let path = match self.selected.clone() {
+            Some(path) => path,
+            None => return,
+        };
+        let map = match FluxMaploader::load_map(path.clone()) {
+            Ok(map) => map,
+            Err(e) => {
+                model.toast_ui.error(format!("Failed to load {}: {}", path, e));
+                return;
+            }
+        };
+        model.game.audio_manager.stop_preview();
+        model.state = FluxState::PlayMap;
+        model.update_rpc = true;
+        model.game.insert_map(map, self.selected_difficulty.clone());
+        model.game.set_autoplay(autoplay);
+        match practice {
+            Some(start_ms) => model.game.start_practice(start_ms),
+            None => model.game.start_audio(),
+        }
+        set_captured(app, model, !autoplay);
     }
 
-    pub fn render(&mut self, app: &App, model: &mut Model, ctx: FrameCtx) {
+    pub fn render(&mut self, app: &App, model: &mut Model, ctx: &FrameCtx) {
+        self.cover_rects = vec![];
+        let mut play = false;
+        let mut autoplay = false;
+        let mut practice = None;
+        // one new cover per frame so opening the menu doesn't stall
+        let mut cover_to_load: Option<String> = None;
+
         egui::Window::new("Map list")
             .fixed_pos(egui::Pos2::new(0.0, 0.0))
-            .default_size(egui::Vec2::new(app.window_rect().w(), app.window_rect().h()))
+            .fixed_size(egui::Vec2::new(app.window_rect().w() * 0.6, app.window_rect().h()))
+            .frame(egui::Frame::none())
             .scroll2([false, true])
             .title_bar(false)
             .resizable(false)
-            .show(&ctx, |ui| {
+            .show(ctx, |ui| {
 
             ui.horizontal(|ui| {
                 ui.label("Search: ");
                 ui.text_edit_singleline(&mut self.map_search);
             });
 
-            ui.label("maps:");
-            for i in self.maps.clone().into_iter() {
-                let mut contains: Vec<bool> = vec![];
-                for s in self.map_search.clone().as_str().split(" ").into_iter() {
-                    if !s.is_empty() {
-                        contains.push(i.contains(s));
-                    }
+            ui.horizontal(|ui| {
+                ui.label("Sort: ");
+                let mut sort = self.sort;
+                egui::ComboBox::from_id_source("map_sort")
+                    .selected_text(sort.name())
+                    .show_ui(ui, |ui| {
+                        for s in FluxMapSort::ALL {
+                            ui.selectable_value(&mut sort, s, s.name());
+                        }
+                    });
+                let reverse = ui.checkbox(&mut self.reverse_sort, "Reverse").changed();
+                if sort != self.sort || reverse {
+                    self.sort = sort;
+                    self.sort_maps();
                 }
-                if contains.contains(&false) && !self.map_search.is_empty() {
+            });
+
+            let mut clicked = None;
+            for entry in self.maps.iter() {
+                if !entry.matches(&self.map_search) {
                     continue;
                 }
-                if ui.add(Button::new(Path::new(&i.clone()).file_name().unwrap().to_str().unwrap().to_string())).clicked() {
-                    let map = FluxMaploader::load_map(i);
-                    model.state = FluxState::PlayMap;
-                    model.update_rpc = true;
-                    model.game.insert_map(map);
-                    model.game.start_audio();
-                    model.captured = true;
-                    let w = app.window(model.window).unwrap();
-
-                    w.set_cursor_visible(!model.captured);
+                let selected = self.selected.as_ref() == Some(&entry.path);
+                let row = ui.horizontal(|ui| {
+                    let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(THUMBNAIL_SIZE), Sense::hover());
+                    if ui.clip_rect().intersects(rect) {
+                        if !self.covers.contains_key(&entry.path) && cover_to_load.is_none() {
+                            cover_to_load = Some(entry.path.clone());
+                        }
+                        self.cover_rects.push((rect, entry.path.clone()));
+                    }
+                    ui.vertical(|ui| {
+                        let title = ui.selectable_label(selected, format!("{} - {}", entry.artist, entry.title));
+                        let stars = entry.hardest().map_or(0.0, |d| d.stars);
+                        let length = entry.hardest().map_or(0, |d| d.length_ms);
+                        ui.add(Label::new(format!("mapped by {} | {} difficulties | up to {:.2}* | {}",
+                            entry.mapper, entry.difficulties.len(), stars, format_length(length))).text_color(Color32::GRAY));
+                        title
+                    }).inner
+                }).inner;
+                if row.clicked() && !selected {
+                    clicked = Some(PathBuf::from(&entry.path));
+                }
+                if row.double_clicked() {
+                    play = true;
                 }
             }
+            if let Some(path) = clicked {
+                self.select(path);
+            }
         });
-    } 
-}
\ No newline at end of file
+
+        let mut mods = model.settings_ui.config.mods.clone();
+        let history = &model.game.history;
+        if let Some(entry) = self.maps.iter().find(|m| Some(&m.path) == self.selected.as_ref()).cloned() {
+            egui::Window::new("Map details")
+                .anchor(egui::Align2::RIGHT_TOP, egui::Vec2::new(-20.0, 20.0))
+                .fixed_size(egui::Vec2::new(COVER_SIZE + 100.0, app.window_rect().h() - 40.0))
+                .frame(egui::Frame::none())
+                .title_bar(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(COVER_SIZE), Sense::hover());
+                    self.cover_rects.push((rect, entry.path.clone()));
+                    ui.heading(&entry.title);
+                    ui.label(format!("Artist: {}", entry.artist));
+                    ui.label(format!("Mapper: {}", entry.mapper));
+
+                    ui.horizontal(|ui| {
+                        ui.label("Difficulty: ");
+                        egui::ComboBox::from_id_source("map_difficulty")
+                            .selected_text(self.selected_difficulty.clone())
+                            .show_ui(ui, |ui| {
+                                for d in entry.difficulties.iter() {
+                                    ui.selectable_value(&mut self.selected_difficulty, d.name.clone(), format!("{} ({:.2}*)", d.name, d.stars));
+                                }
+                            });
+                    });
+                    let length_ms = match entry.difficulties.iter().find(|d| d.name == self.selected_difficulty) {
+                        Some(d) => {
+                            ui.label(format!("Stars: {:.2}*", d.stars));
+                            ui.label(format!("Length: {}", format_length(d.length_ms)));
+                            ui.label(format!("Notes: {}", d.note_count));
+                            d.length_ms
+                        },
+                        None => 0,
+                    };
+                    ui.horizontal_wrapped(|ui| {
+                        for m in FluxMod::ALL {
+                            if ui.selectable_label(mods.has(m), m.name()).clicked() {
+                                mods.set(m, !mods.has(m));
+                            }
+                        }
+                    });
+                    ui.label(format!("Score multiplier: {:.2}x", mods.score_multiplier()));
+                    ui.horizontal(|ui| {
+                        if ui.button("Play").clicked() {
+                            play = true;
+                        }
+                        if ui.button("Autoplay").clicked() {
+                            play = true;
+                            autoplay = true;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Practice").clicked() {
+                            play = true;
+                            practice = Some(self.practice_start * 1000.0);
+                        }
+                        ui.label("from");
+                        ui.add(egui::DragValue::new(&mut self.practice_start)
+                            .speed(0.5)
+                            .clamp_range(0.0..=length_ms as f64 / 1000.0)
+                            .suffix("s"));
+                    });
+
+                    ui.separator();
+                    let best = history.best(&entry.hash, &self.selected_difficulty);
+                    let best_accuracy = history.best_accuracy(&entry.hash, &self.selected_difficulty);
+                    match (best, best_accuracy) {
+                        (Some(best), Some(best_accuracy)) => {
+                            ui.label(format!("Best score: {} ({:.2}%)", best.score, best.accuracy));
+                            ui.label(format!("Best accuracy: {:.2}%", best_accuracy.accuracy));
+                        },
+                        _ => { ui.add(Label::new("Not cleared yet").text_color(Color32::GRAY)); },
+                    }
+                    let leaderboard = history.leaderboard(&entry.hash, &self.selected_difficulty);
+                    if !leaderboard.is_empty() {
+                        ui.label(format!("Leaderboard ({} plays)", leaderboard.len()));
+                        for (i, score) in leaderboard.iter().take(LEADERBOARD_SIZE).enumerate() {
+                            let text = format!("#{} {}", i + 1, format_score(score));
+                            if score.failed {
+                                ui.add(Label::new(format!("{} | failed", text)).text_color(Color32::GRAY));
+                            } else {
+                                ui.label(text);
+                            }
+                        }
+                    }
+                });
+        }
+
+        if mods != model.settings_ui.config.mods {
+            model.settings_ui.config.mods = mods;
+            model.game.new_config(model.settings_ui.config.clone());
+            model.settings_ui.save();
+        }
+        if let Some(path) = self.selected.clone() {
+            self.load_cover(app, &path);
+            model.game.audio_manager.preview_map(path);
+        }
+        if let Some(path) = cover_to_load {
+            self.load_cover(app, &path);
+        }
+        if play {
+            self.play_selected(app, model, autoplay, practice);
+        }
+    }
+
+    /// draws the cover art under the spots the ui left for it
+    pub fn draw(&self, app: &App, draw: Draw) {
+        let win = app.window_rect();
+        for (rect, path) in self.cover_rects.iter() {
+            if let Some(Some(tex)) = self.covers.get(path) {
+                draw.texture(tex)
+                    .x_y(rect.center().x - win.w() / 2.0, win.h() / 2.0 - rect.center().y)
+                    .w_h(rect.width(), rect.height());
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
+pub mod fail;
 pub mod hud;
 pub mod settings;
-pub mod map_menu;
\ No newline at end of file
+pub mod map_menu;
+pub mod pause;
+pub mod results;
+pub mod toast;
\ No newline at end of file
@@ -0,0 +1,46 @@
+use nannou_egui::{egui::{self, Label}, FrameCtx};
+
+use crate::core::game::FluxGame;
+
+pub enum FluxPauseAction {
+    Resume,
+    Retry,
+    Quit,
+}
+
+/// shown over the play area while the game is paused
+pub struct FluxPauseUI;
+
+impl FluxPauseUI {
+    pub fn render(ctx: &FrameCtx, game: &FluxGame) -> Option<FluxPauseAction> {
+        let mut action = None;
+        let stats = &game.stats;
+        let engine = &game.notes_manager.engine;
+        egui::Window::new("Paused")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .title_bar(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.add(Label::new("PAUSED").heading());
+                let ms = game.time_manager.song_timer.current_ms.max(0.0) as u64;
+                ui.add(egui::ProgressBar::new(game.progress() as f32)
+                    .text(format!("{}:{:02}", ms / 60000, (ms / 1000) % 60)));
+                ui.label(format!("Notes: {}/{}", stats.note_total, engine.note_count()));
+                ui.label(format!("Accuracy: {:.2}%", stats.accuracy()));
+                ui.label(format!("Score: {}", stats.score));
+                ui.label(format!("Combo: {}x (max {}x)", stats.combo, stats.max_combo));
+                ui.horizontal(|ui| {
+                    if ui.button("Resume").clicked() {
+                        action = Some(FluxPauseAction::Resume);
+                    }
+                    if ui.button("Retry").clicked() {
+                        action = Some(FluxPauseAction::Retry);
+                    }
+                    if ui.button("Quit").clicked() {
+                        action = Some(FluxPauseAction::Quit);
+                    }
+                });
+            });
+        action
+    }
+}
@@ -0,0 +1,187 @@
+use nannou_egui::{egui::{self, Color32, Label, Stroke, pos2, vec2}, FrameCtx};
+
+use flux_engine::{judgement::{FluxJudgement, FluxJudgementConfig}, stats::FluxStats};
+use flux_map::FluxMap;
+
+use crate::core::{game::FluxGame, history::FluxScore};
+
+const GRAPH_WIDTH: f32 = 500.0;
+const GRAPH_HEIGHT: f32 = 120.0;
+const HISTOGRAM_BINS: usize = 41;
+
+pub enum FluxResultsAction {
+    Retry,
+    WatchReplay,
+    Menu,
+}
+
+pub fn judgement_color(judgement: FluxJudgement) -> Color32 {
+    match judgement {
+        FluxJudgement::Perfect => Color32::from_rgb(90, 200, 255),
+        FluxJudgement::Great => Color32::from_rgb(90, 230, 120),
+        FluxJudgement::Good => Color32::from_rgb(240, 200, 80),
+        FluxJudgement::Miss => Color32::from_rgb(230, 60, 60),
+    }
+}
+
+/// summary of a finished play, taken when the results screen opens
+pub struct FluxResultsUI {
+    title: String,
+    difficulty: String,
+    stats: FluxStats,
+    windows: FluxJudgementConfig,
+    mods: String,
+    speed: f64,
+    length_ms: f64,
+    has_replay: bool,
+    previous_best: Option<FluxScore>,
+    /// whether the play went into the history, replays and autoplay don't
+    saved: bool,
+}
+
+impl FluxResultsUI {
+    pub fn new(game: &FluxGame) -> Self {
+        let artist = game.map.get_meta_string("artist").unwrap_or_default();
+        let song = game.map.get_meta_string("song_name").unwrap_or_default();
+        let length_ms = game.map.difficulties.get(&game.difficulty)
+            .map(|notes| FluxMap::length_ms(notes))
+            .unwrap_or(0) as f64;
+        Self {
+            title: format!("{} - {}", artist, song),
+            difficulty: game.difficulty.clone(),
+            stats: game.stats.clone(),
+            windows: game.notes_manager.engine.config.judgement.clone(),
+            mods: game.notes_manager.engine.config.mods.names(),
+            speed: game.config.audio.speed,
+            length_ms,
+            has_replay: game.replay_manager.last.is_some(),
+            previous_best: game.previous_best.clone(),
+            saved: !game.replay_manager.watched() && game.autoplay.is_none(),
+        }
+    }
+
+    pub fn render(&self, ctx: &FrameCtx) -> Option<FluxResultsAction> {
+        let mut action = None;
+        let stats = &self.stats;
+        egui::Window::new("Results")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .title_bar(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.add(Label::new(&self.title).heading());
+                ui.label(format!("[{}] {:.2}x", self.difficulty, self.speed));
+                if !self.mods.is_empty() {
+                    ui.label(format!("Mods: {}", self.mods));
+                }
+                ui.separator();
+                ui.add(Label::new(format!("{:.2}%", stats.accuracy())).heading());
+                ui.label(format!("Score: {}", stats.score));
+                ui.label(format!("Max combo: {}", stats.max_combo));
+                ui.label(format!("Misses: {}", stats.notes_missed));
+                if self.saved {
+                    self.compare(ui);
+                }
+                ui.horizontal(|ui| {
+                    for judgement in FluxJudgement::ALL {
+                        ui.add(Label::new(format!("{}: {}", judgement.name(), stats.judgements[judgement.index()])).text_color(judgement_color(judgement)));
+                    }
+                });
+
+                ui.separator();
+                ui.label(format!("Hit error, mean {:+.1}ms", stats.mean_error()));
+                self.histogram(ui);
+                ui.label("Timing");
+                self.timing_graph(ui);
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Retry").clicked() {
+                        action = Some(FluxResultsAction::Retry);
+                    }
+                    if self.has_replay && ui.button("Watch replay").clicked() {
+                        action = Some(FluxResultsAction::WatchReplay);
+                    }
+                    if ui.button("Back to menu").clicked() {
+                        action = Some(FluxResultsAction::Menu);
+                    }
+                });
+            });
+        action
+    }
+
+    /// the play against the best one before it
+    fn compare(&self, ui: &mut egui::Ui) {
+        let best = match &self.previous_best {
+            Some(best) => best,
+            None => {
+                ui.add(Label::new("First clear!").text_color(Color32::GOLD));
+                return;
+            }
+        };
+        if self.stats.score > best.score {
+            ui.add(Label::new("New best!").text_color(Color32::GOLD));
+        }
+        ui.label(format!("Previous best: {} ({:.2}%) {}", best.score, best.accuracy, best.age()));
+        let score_diff = self.stats.score as i64 - best.score as i64;
+        let accuracy_diff = self.stats.accuracy() - best.accuracy;
+        let color = |diff: f64| if diff >= 0.0 { Color32::GREEN } else { Color32::RED };
+        ui.horizontal(|ui| {
+            ui.add(Label::new(format!("{:+}", score_diff)).text_color(color(score_diff as f64)));
+            ui.add(Label::new(format!("{:+.2}%", accuracy_diff)).text_color(color(accuracy_diff)));
+        });
+    }
+
+    /// how many hits landed at each error, early on the left
+    fn histogram(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(vec2(GRAPH_WIDTH, GRAPH_HEIGHT), egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+        let good = self.windows.good.max(1.0);
+        let mut bins = [0usize; HISTOGRAM_BINS];
+        for error in self.stats.hits.iter().filter_map(|h| h.error) {
+            let bin = ((error + good) / (good * 2.0) * HISTOGRAM_BINS as f64).max(0.0) as usize;
+            bins[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+        let max = bins.iter().copied().max().unwrap_or(0).max(1);
+        let bar_width = rect.width() / HISTOGRAM_BINS as f32;
+        for (i, count) in bins.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let error = -good + good * 2.0 * (i as f64 + 0.5) / HISTOGRAM_BINS as f64;
+            let height = rect.height() * *count as f32 / max as f32;
+            let left = rect.left() + i as f32 * bar_width;
+            painter.rect_filled(
+                egui::Rect::from_min_max(pos2(left + 1.0, rect.bottom() - height), pos2(left + bar_width - 1.0, rect.bottom())),
+                0.0,
+                judgement_color(FluxJudgement::from_error(error, &self.windows)));
+        }
+        painter.line_segment([pos2(rect.center().x, rect.top()), pos2(rect.center().x, rect.bottom())], Stroke::new(1.0, Color32::WHITE));
+    }
+
+    /// every hit's error over the song, misses are marked across the whole graph
+    fn timing_graph(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(vec2(GRAPH_WIDTH, GRAPH_HEIGHT), egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+        let good = self.windows.good.max(1.0);
+        let length = self.length_ms.max(1.0);
+        let y_of = |error: f64| rect.center().y + (error / good) as f32 * rect.height() / 2.0;
+        for window in [self.windows.perfect, self.windows.great] {
+            for error in [-window, window] {
+                painter.line_segment([pos2(rect.left(), y_of(error)), pos2(rect.right(), y_of(error))], Stroke::new(1.0, Color32::from_gray(50)));
+            }
+        }
+        painter.line_segment([pos2(rect.left(), rect.center().y), pos2(rect.right(), rect.center().y)], Stroke::new(1.0, Color32::from_gray(120)));
+
+        for hit in &self.stats.hits {
+            let x = rect.left() + (hit.note_ms / length).clamp(0.0, 1.0) as f32 * rect.width();
+            match hit.error {
+                Some(error) => painter.circle_filled(pos2(x, y_of(error.clamp(-good, good))), 2.0, judgement_color(hit.judgement)),
+                None => painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], Stroke::new(1.0, judgement_color(FluxJudgement::Miss).linear_multiply(0.5))),
+            }
+        }
+    }
+}
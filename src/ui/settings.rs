@@ -1,33 +1,54 @@
 use std::path::{Path, PathBuf};
 
+use flux_engine::autoplay::FluxEasing;
 use nannou::App;
 use nannou_egui::{egui::{self, DragValue}, FrameCtx};
 
-use crate::{core::{config::FluxConfig, constants::{NOTESETS_DIR, CURSORSETS_DIR, HITSETS_DIR}}, DEFAULT_CONFIG, Model};
+use crate::{core::{config::{FluxConfig, FluxApproachMode, FluxCameraMode, FluxCursorMode, FluxOpacityCurve}, constants::{NOTESETS_DIR, CURSORSETS_DIR, HITSETS_DIR}}, sets::{manifest::FluxColor, skin::FluxSkin}, Model};
 
 #[derive(Clone)]
 pub struct FluxSettingsUI {
     pub selected_noteset: String,
     pub selected_cursorset: String,
     pub selected_hitset: String,
+    /// empty for no skin
+    pub selected_skin: String,
     pub show_settings: bool,
     pub notesets: Vec<String>,
     pub hitsets: Vec<String>,
     pub cursorsets: Vec<String>,
+    pub skins: Vec<String>,
     pub config: FluxConfig,
+    pub config_path: PathBuf,
+    /// the config changed and hasn't been saved yet
+    dirty: bool,
+}
+
+/// the set directory matching the configured name, the first one otherwise
+fn find_set(sets: &Vec<String>, name: &str) -> String {
+    sets.iter()
+        .find(|s| Path::new(s).file_name().map_or(false, |f| f == name))
+        .unwrap_or(&sets[0])
+        .clone()
+}
+
+fn set_name(path: &str) -> String {
+    Path::new(path).file_name().unwrap().to_str().unwrap().to_string()
 }
 
 impl FluxSettingsUI {
-    pub fn new() -> Self {
-        let mut config = DEFAULT_CONFIG;
-        config.note.approach_time = config.note.ad / config.note.ar;
+    pub fn new(config: FluxConfig, config_path: PathBuf) -> Self {
         Self {
             config,
+            config_path,
+            dirty: false,
             show_settings: false,
             selected_noteset: String::from(""),
             selected_cursorset: String::from(""),
             selected_hitset: String::from(""),
+            selected_skin: String::new(),
             cursorsets: vec![],
+            skins: vec![],
             notesets: vec![],
             hitsets: vec![],
         }
@@ -54,28 +75,126 @@ impl FluxSettingsUI {
             }
         }
 
-        self.selected_noteset = model.settings_ui.notesets[0].clone();
-        self.selected_hitset = model.settings_ui.hitsets[0].clone();
-        self.selected_cursorset = model.settings_ui.cursorsets[0].clone();
-        model.game.noteset.load_from_path(app, PathBuf::from(model.settings_ui.selected_noteset.clone()));
-        model.game.hitset.load_from_path(PathBuf::from(model.settings_ui.selected_hitset.clone()));
-        model.game.cursorset.load_from_path(app, PathBuf::from(model.settings_ui.selected_cursorset.clone()));
+        self.selected_noteset = find_set(&self.notesets, &self.config.sets.note);
+        self.selected_hitset = find_set(&self.hitsets, &self.config.sets.hit);
+        self.selected_cursorset = find_set(&self.cursorsets, &self.config.sets.cursor);
+        self.skins = FluxSkin::list().iter().map(|p| String::from(p.to_str().unwrap())).collect();
+        self.selected_skin = self.skins.iter()
+            .find(|s| !self.config.sets.skin.is_empty() && set_name(s) == self.config.sets.skin)
+            .cloned()
+            .unwrap_or_default();
+        model.game.audio_manager.set_song_volume(self.config.audio.volume);
+        model.game.audio_manager.set_preview_volume(self.config.audio.volume);
+        model.game.cursor.change_cursor_size(self.config.cursor.size);
+        self.load_sets(app, model);
+    }
+
+    /// loads the selected skin and sets into the game
+    fn load_sets(&self, app: &App, model: &mut Model) {
+        let skin = Some(PathBuf::from(&self.selected_skin)).filter(|_| !self.selected_skin.is_empty());
+        if let Err(e) = model.game.load_sets(
+            app,
+            skin.as_deref(),
+            Path::new(&self.selected_noteset),
+            Path::new(&self.selected_hitset),
+            Path::new(&self.selected_cursorset)) {
+            model.toast_ui.error(format!("Failed to load skin {}: {}", set_name(&self.selected_skin), e));
+        }
+    }
+
+    pub fn save(&self) {
+        self.config.save(&self.config_path);
     }
 
-    pub fn render(&mut self, app: &App, model: &mut Model, ctx: FrameCtx) {
+    pub fn render(&mut self, app: &App, model: &mut Model, ctx: &FrameCtx) {
         if self.show_settings {
-            egui::Window::new("Settings").resizable(false).show(&ctx, |ui| {
+            egui::Window::new("Settings").resizable(false).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Approach: ");
+                    let note = &mut self.config.note;
+                    let before = note.approach;
+                    egui::ComboBox::from_id_source("approach_mode")
+                        .selected_text(before.name())
+                        .show_ui(ui, |ui| {
+                            for mode in FluxApproachMode::ALL {
+                                ui.selectable_value(&mut note.approach, mode, mode.name());
+                            }
+                        });
+                    let speed = match note.approach {
+                        FluxApproachMode::Rate => ui.add(DragValue::new(&mut note.ar).prefix("AR ").speed(0.1).clamp_range(0.1..=500.0)),
+                        FluxApproachMode::Time => ui.add(DragValue::new(&mut note.approach_ms).suffix("ms").speed(1.0).clamp_range(50.0..=10000.0)),
+                    };
+                    let changed = [
+                        note.approach != before,
+                        speed.changed(),
+                        ui.add(DragValue::new(&mut note.ad).prefix("AD ").speed(0.1).clamp_range(0.1..=500.0)).changed(),
+                    ];
+                    ui.label(format!("{:.0}ms", note.approach_time()));
+                    if changed.contains(&true) {
+                        model.game.new_config(self.config.clone());
+                        self.dirty = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Fade in: ");
+                    let note = &mut self.config.note;
+                    let before = note.opacity_curve;
+                    let changed = [
+                        ui.add(DragValue::new(&mut note.fade_in).speed(0.05).clamp_range(0.0..=500.0)).changed(),
+                        ui.add(DragValue::new(&mut note.pushback).prefix("pushback ").suffix("ms").speed(1.0).clamp_range(0.0..=2000.0)).changed(),
+                    ];
+                    egui::ComboBox::from_id_source("opacity_curve")
+                        .selected_text(before.name())
+                        .show_ui(ui, |ui| {
+                            for curve in FluxOpacityCurve::ALL {
+                                ui.selectable_value(&mut note.opacity_curve, curve, curve.name());
+                            }
+                        });
+                    if changed.contains(&true) || note.opacity_curve != before {
+                        model.game.new_config(self.config.clone());
+                        self.dirty = true;
+                    }
+                });
+
                 ui.horizontal(|ui| {
-                    ui.label("AR: ");
-                    if ui.add(DragValue::new(&mut self.config.note.ar).speed(0.1).clamp_range(0.0..=500.0)).changed() {
+                    ui.label("Note colors: ");
+                    let mut changed = false;
+                    let mut remove = None;
+                    for (i, color) in self.config.note.colors.iter_mut().enumerate() {
+                        changed |= ui.color_edit_button_rgba_unmultiplied(&mut color.0).changed();
+                        if ui.small_button("x").clicked() {
+                            remove = Some(i);
+                        }
+                    }
+                    if let Some(i) = remove {
+                        self.config.note.colors.remove(i);
+                        changed = true;
+                    }
+                    if ui.small_button("+").clicked() {
+                        self.config.note.colors.push(FluxColor::WHITE);
+                        changed = true;
+                    }
+                    changed |= ui.checkbox(&mut self.config.note.map_colors, "Use map colors").changed();
+                    if changed {
                         model.game.new_config(self.config.clone());
+                        self.dirty = true;
                     }
-                }); 
+                });
 
                 ui.horizontal(|ui| {
-                    ui.label("AD: ");
-                    if ui.add(DragValue::new(&mut self.config.note.ad).speed(0.1).clamp_range(0.0..=500.0)).changed() {
+                    ui.label("Effects: ");
+                    let effects = &mut self.config.effects;
+                    let changed = [
+                        ui.checkbox(&mut effects.hit, "Hits").changed(),
+                        ui.checkbox(&mut effects.miss, "Misses").changed(),
+                        ui.checkbox(&mut effects.popups, "Judgements").changed(),
+                        ui.checkbox(&mut effects.hide_perfect_popups, "Hide perfect").changed(),
+                        ui.checkbox(&mut effects.combo_pulse, "Combo pulse").changed(),
+                    ];
+                    if changed.contains(&true) {
                         model.game.new_config(self.config.clone());
+                        self.dirty = true;
                     }
                 });
 
@@ -83,6 +202,7 @@ impl FluxSettingsUI {
                     ui.label("Cursor Size: ");
                     if ui.add(DragValue::new(&mut self.config.cursor.size).speed(0.1).clamp_range(0.0..=500.0)).changed() {
                         model.game.cursor.change_cursor_size(self.config.cursor.size);
+                        self.dirty = true;
                     }
                 });
 
@@ -90,18 +210,111 @@ impl FluxSettingsUI {
                     ui.label("Volume: ");
                     if ui.add(DragValue::new(&mut self.config.audio.volume).clamp_range(0.0..=10.0).speed(0.01)).changed() {
                         model.game.audio_manager.set_song_volume(self.config.audio.volume);
+                        model.game.audio_manager.set_preview_volume(self.config.audio.volume);
+                        self.dirty = true;
                     }
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("Sensitivity: ");
-                    ui.add(DragValue::new(&mut self.config.cursor.sens).speed(0.1));
+                    if ui.add(DragValue::new(&mut self.config.cursor.sens).speed(0.1)).changed() {
+                        self.dirty = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Camera: ");
+                    let camera = &mut self.config.camera;
+                    let before = camera.mode;
+                    egui::ComboBox::from_id_source("camera_mode")
+                        .selected_text(before.name())
+                        .show_ui(ui, |ui| {
+                            for mode in FluxCameraMode::ALL {
+                                ui.selectable_value(&mut camera.mode, mode, mode.name());
+                            }
+                        });
+                    let changed = [
+                        camera.mode != before,
+                        ui.add(DragValue::new(&mut camera.parallax).prefix("parallax ").speed(0.01).clamp_range(0.0..=1.0)).changed(),
+                        ui.add(DragValue::new(&mut camera.fov).prefix("fov ").suffix("°").speed(0.5).clamp_range(10.0..=150.0)).changed(),
+                    ];
+                    if changed.contains(&true) {
+                        model.game.new_config(self.config.clone());
+                        self.dirty = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Cursor mode: ");
+                    let before = self.config.cursor.mode;
+                    egui::ComboBox::from_id_source("cursor_mode")
+                        .selected_text(before.name())
+                        .show_ui(ui, |ui| {
+                            for mode in FluxCursorMode::ALL {
+                                ui.selectable_value(&mut self.config.cursor.mode, mode, mode.name());
+                            }
+                        });
+                    if self.config.cursor.mode != before {
+                        self.dirty = true;
+                    }
                 });
 
+                if self.config.cursor.mode == FluxCursorMode::Absolute {
+                    ui.horizontal(|ui| {
+                        ui.label("Region: ");
+                        let region = &mut self.config.cursor.region;
+                        let changed = [
+                            ui.add(DragValue::new(&mut region.x).prefix("x ").speed(0.01).clamp_range(0.0..=1.0)).changed(),
+                            ui.add(DragValue::new(&mut region.y).prefix("y ").speed(0.01).clamp_range(0.0..=1.0)).changed(),
+                            ui.add(DragValue::new(&mut region.w).prefix("w ").speed(0.01).clamp_range(0.01..=1.0)).changed(),
+                            ui.add(DragValue::new(&mut region.h).prefix("h ").speed(0.01).clamp_range(0.01..=1.0)).changed(),
+                        ];
+                        if changed.contains(&true) {
+                            self.dirty = true;
+                        }
+                    });
+                }
+
                 ui.horizontal(|ui| {
                     ui.label("Speed: ");
                     if ui.add(DragValue::new(&mut self.config.audio.speed).speed(0.01).clamp_range(0.0..=10.0)).changed() {
                         model.game.set_speed(self.config.audio.speed);
+                        self.dirty = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Autoplay easing: ");
+                    let before = self.config.misc.autoplay_easing;
+                    egui::ComboBox::from_id_source("autoplay_easing")
+                        .selected_text(before.name())
+                        .show_ui(ui, |ui| {
+                            for easing in FluxEasing::ALL {
+                                ui.selectable_value(&mut self.config.misc.autoplay_easing, easing, easing.name());
+                            }
+                        });
+                    if self.config.misc.autoplay_easing != before {
+                        model.game.new_config(self.config.clone());
+                        self.dirty = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Skin: ");
+                    let before = self.selected_skin.clone();
+                    let name = |s: &String| if s.is_empty() { String::from("None") } else { set_name(s) };
+                    egui::ComboBox::from_id_source("skin")
+                        .selected_text(name(&self.selected_skin))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.selected_skin, String::new(), "None");
+                            for v in self.skins.clone().into_iter() {
+                                ui.selectable_value(&mut self.selected_skin, v.clone(), name(&v));
+                            }
+                        });
+                    if self.selected_skin != before {
+                        self.load_sets(app, model);
+                        self.config.sets.skin = if self.selected_skin.is_empty() { String::new() } else { set_name(&self.selected_skin) };
+                        self.dirty = true;
                     }
                 });
 
@@ -115,7 +328,9 @@ impl FluxSettingsUI {
                             }
                         });
                     if ui.button("Load selected noteset").clicked() {
-                        model.game.noteset.load_from_path(app, PathBuf::from(self.selected_noteset.clone()));
+                        self.load_sets(app, model);
+                        self.config.sets.note = set_name(&self.selected_noteset);
+                        self.dirty = true;
                     }
                 });
 
@@ -129,7 +344,9 @@ impl FluxSettingsUI {
                             }
                         });
                     if ui.button("Load selected hitset").clicked() {
-                        model.game.hitset.load_from_path(PathBuf::from(self.selected_hitset.clone()));
+                        self.load_sets(app, model);
+                        self.config.sets.hit = set_name(&self.selected_hitset);
+                        self.dirty = true;
                     }
                 });
 
@@ -139,14 +356,22 @@ impl FluxSettingsUI {
                         .selected_text(format!("{:?}", Path::new(&self.selected_cursorset).file_name().unwrap().to_str().unwrap().to_string()))
                         .show_ui(ui, |ui| {
                             for  v in self.cursorsets.clone().into_iter() {
-                                ui.selectable_value(&mut self.selected_noteset, v.clone(), Path::new(&v).into_iter().last().unwrap().to_str().unwrap().to_string());
+                                ui.selectable_value(&mut self.selected_cursorset, v.clone(), Path::new(&v).into_iter().last().unwrap().to_str().unwrap().to_string());
                             }
                         });
                     if ui.button("Load selected cursorset").clicked() {
-                        model.game.cursorset.load_from_path(app, PathBuf::from(self.selected_cursorset.clone()));
+                        self.load_sets(app, model);
+                        self.config.sets.cursor = set_name(&self.selected_cursorset);
+                        self.dirty = true;
                     }
                 });
             });
         }
+
+        // drags and typing change the config every frame, it's written once they're done
+        if self.dirty && !ctx.input().pointer.any_down() && ctx.memory().focus().is_none() {
+            self.save();
+            self.dirty = false;
+        }
     }
 }
\ No newline at end of file
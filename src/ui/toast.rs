@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+use nannou_egui::{egui::{self, Color32}, FrameCtx};
+
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+struct FluxToast {
+    text: String,
+    error: bool,
+    created: Instant,
+}
+
+pub struct FluxToastUI {
+    toasts: Vec<FluxToast>,
+}
+
+impl FluxToastUI {
+    pub fn new() -> Self {
+        Self {
+            toasts: vec![],
+        }
+    }
+
+    pub fn info(&mut self, text: String) {
+        self.toasts.push(FluxToast { text, error: false, created: Instant::now() });
+    }
+
+    pub fn error(&mut self, text: String) {
+        log::error!("{}", text);
+        self.toasts.push(FluxToast { text, error: true, created: Instant::now() });
+    }
+
+    pub fn render(&mut self, ctx: &FrameCtx) {
+        self.toasts.retain(|t| t.created.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Window::new("Toasts")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10.0, -10.0))
+            .title_bar(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for toast in self.toasts.iter() {
+                    let color = if toast.error { Color32::RED } else { Color32::WHITE };
+                    ui.colored_label(color, &toast.text);
+                }
+            });
+    }
+}